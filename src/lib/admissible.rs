@@ -0,0 +1,196 @@
+use super::prelude::*;
+
+// constraint, which doesn't allow the state
+#[derive(Clone, Debug)]
+pub enum Limit {
+    // carcass muscle is out of the joint limit (`Carcass::check_diff`)
+    Joint,
+    // muscle with the index would be shorter than `MIN_PART` of its initial length
+    MinPart(usize),
+    // or longer than `MAX_PART`
+    MaxPart(usize),
+    // deformation of the muscle fails
+    Solver(usize, DeformError),
+}
+
+impl Limit {
+    fn describe(&self, names: &[String]) -> String {
+        match self {
+            Limit::Joint => "carcass: joint limit".to_string(),
+            Limit::MinPart(i) => format!("{}: shorter than MIN_PART", names[*i]),
+            Limit::MaxPart(i) => format!("{}: longer than MAX_PART", names[*i]),
+            Limit::Solver(i, err) => format!("{}: {}", names[*i], err).replace('\n', " "),
+        }
+    }
+}
+
+// state of the scene with given angle in the elbow
+#[derive(Clone, Debug)]
+pub struct RangeSample {
+    pub angle: f64,
+    // length of the muscle of the carcass
+    pub len: f64,
+    // empty for the admissible state
+    pub limits: Vec<Limit>,
+}
+
+pub struct AdmissibleRange {
+    names: Vec<String>,
+    // angles allowed by the carcass and by `MIN_PART`/`MAX_PART` of every muscle, None
+    // for the bound, which isn't reached in the joint
+    pub carcass: (f64, f64),
+    pub muscles: Vec<(Option<f64>, Option<f64>)>,
+    pub samples: Vec<RangeSample>,
+    // admissible samples nearest to the current angle
+    pub range: Option<(f64, f64)>,
+}
+
+// Checks `samples` states with angles evenly spaced over [0, PI]: every muscle is
+// deformed from its current state to the length given by its attachment.
+pub fn explore(muscles: &[SceneMuscle], carcass: &Carcass, samples: usize) -> AdmissibleRange {
+    let att = carcass.default_attachment();
+    let cur_len = carcass.muscle_len(&att);
+    let bounds: Vec<(Option<f64>, Option<f64>)> = muscles
+        .iter()
+        .map(|m| {
+            let (min, max) = m.muscle().lock().unwrap().len_range();
+            let att = m.attachment();
+            let (a1, a2) = (
+                reachable_angle(carcass, att, min),
+                reachable_angle(carcass, att, max),
+            );
+            // length grows with the angle, if the attachment points are on one side
+            if att.origin * att.insertion > 0_f64 {
+                (a1, a2)
+            } else {
+                (a2, a1)
+            }
+        })
+        .collect();
+
+    let samples: Vec<RangeSample> = (0..samples)
+        .map(|i| std::f64::consts::PI * i as f64 / (samples - 1).max(1) as f64)
+        .map(|angle| {
+            let len = carcass.muscle_len_at(&att, angle);
+            let mut limits = Vec::new();
+            if !carcass.check_diff(len - cur_len) {
+                limits.push(Limit::Joint);
+            }
+            for (i, m) in muscles.iter().enumerate() {
+                let mut muscle = m.muscle().lock().unwrap().clone();
+                let diff = carcass.muscle_len_at(m.attachment(), angle) - muscle.len();
+                let (min, max) = muscle.len_range();
+                if muscle.len() + diff < min {
+                    limits.push(Limit::MinPart(i));
+                } else if muscle.len() + diff > max {
+                    limits.push(Limit::MaxPart(i));
+                } else if let Err(err) = muscle.deform(diff) {
+                    limits.push(Limit::Solver(i, err));
+                }
+            }
+            RangeSample { angle, len, limits }
+        })
+        .collect();
+
+    let range = admissible_run(&samples, carcass.angle());
+    AdmissibleRange {
+        names: muscles.iter().map(|m| m.name().to_string()).collect(),
+        carcass: carcass.angle_range(),
+        muscles: bounds,
+        samples,
+        range,
+    }
+}
+
+// angle, in which the muscle has the length `len`, None if the joint doesn't reach it
+fn reachable_angle(carcass: &Carcass, att: &Attachment, len: f64) -> Option<f64> {
+    let (min, max) = carcass.angle_range();
+    validate_triangle(att, len).ok()?;
+    Some(carcass.angle_for_len(att, len)).filter(|angle| *angle >= min && *angle <= max)
+}
+
+fn format_bound(angle: Option<f64>) -> String {
+    match angle {
+        Some(angle) => format!("{:.1}°", angle.to_degrees()),
+        None => "unreachable".to_string(),
+    }
+}
+
+// Angles of the run of admissible samples nearest to `angle` (the current state can lie
+// on the bound of the carcass, which itself isn't allowed by `check_diff`).
+fn admissible_run(samples: &[RangeSample], angle: f64) -> Option<(f64, f64)> {
+    let nearest = (0..samples.len())
+        .filter(|&i| samples[i].limits.is_empty())
+        .min_by(|&i, &j| {
+            let (a, b) = (samples[i].angle - angle, samples[j].angle - angle);
+            a.abs().partial_cmp(&b.abs()).unwrap()
+        })?;
+    let mut first = nearest;
+    while first > 0 && samples[first - 1].limits.is_empty() {
+        first -= 1;
+    }
+    let mut last = nearest;
+    while last + 1 < samples.len() && samples[last + 1].limits.is_empty() {
+        last += 1;
+    }
+    Some((samples[first].angle, samples[last].angle))
+}
+
+impl AdmissibleRange {
+    // samples, where constraints begin and stop to be limiting, in ascending order
+    pub fn transitions(&self) -> Vec<(&RangeSample, String)> {
+        let mut res = Vec::new();
+        let describe = |s: &RangeSample| -> Vec<String> {
+            s.limits.iter().map(|l| l.describe(&self.names)).collect()
+        };
+        if let Some(first) = self.samples.first() {
+            for text in describe(first) {
+                res.push((first, format!("limited by {}", text)));
+            }
+        }
+        for pair in self.samples.windows(2) {
+            let (prev, next) = (describe(&pair[0]), describe(&pair[1]));
+            for text in prev.iter().filter(|text| !next.contains(text)) {
+                res.push((&pair[1], format!("released {}", text)));
+            }
+            for text in next.iter().filter(|text| !prev.contains(text)) {
+                res.push((&pair[1], format!("limited by {}", text)));
+            }
+        }
+        res
+    }
+
+    pub fn report(&self) -> String {
+        let mut text = match self.range {
+            Some((min, max)) => format!(
+                "Admissible angles: [{:.1}°, {:.1}°]\n",
+                min.to_degrees(),
+                max.to_degrees()
+            ),
+            None => "No admissible states!\n".to_string(),
+        };
+        text += &format!(
+            "Carcass: ({:.1}°, {:.1}°)\n",
+            self.carcass.0.to_degrees(),
+            self.carcass.1.to_degrees()
+        );
+        for (name, (min, max)) in self.names.iter().zip(self.muscles.iter()) {
+            text += &format!(
+                "{} (MIN_PART, MAX_PART): [{}, {}]\n",
+                name,
+                format_bound(*min),
+                format_bound(*max)
+            );
+        }
+        text += &format!("\nSamples: {}\n", self.samples.len());
+        for (sample, change) in self.transitions() {
+            text += &format!(
+                "{:>6.1}° (len {:.1}): {}\n",
+                sample.angle.to_degrees(),
+                sample.len,
+                change
+            );
+        }
+        text
+    }
+}
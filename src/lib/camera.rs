@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewPreset {
+    Front,
+    Side,
+    Top,
+    // arm hangs down with the shoulder at the top of the screen
+    Anatomical,
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 4] = [
+        ViewPreset::Front,
+        ViewPreset::Side,
+        ViewPreset::Top,
+        ViewPreset::Anatomical,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewPreset::Front => "Front",
+            ViewPreset::Side => "Side",
+            ViewPreset::Top => "Top",
+            ViewPreset::Anatomical => "Anatomical",
+        }
+    }
+
+    // (yaw, pitch, roll)
+    fn angles(&self) -> (f64, f64, f64) {
+        let right = std::f64::consts::FRAC_PI_2;
+        match self {
+            ViewPreset::Front => (0_f64, 0_f64, 0_f64),
+            ViewPreset::Side => (right, 0_f64, 0_f64),
+            ViewPreset::Top => (0_f64, right, 0_f64),
+            ViewPreset::Anatomical => (0_f64, 0_f64, -right),
+        }
+    }
+}
+
+// point of the model, around which the camera rotates and scales
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Pivot {
+    // center of the bounding box of the muscles and the carcass
+    SceneCenter,
+    Elbow,
+    // (muscle, node)
+    Node(usize, usize),
+    Point(Point3d),
+}
+
+// Orbit camera: the target is shown in the center of the screen, the model is rotated
+// around it by yaw (Y), pitch (X) and roll (Z) and scaled by `CAMERA_DISTANCE / distance`.
+// The view matrix is built from these values every time, so nothing accumulates. The
+// projection is parallel: the renderer has no perspective division.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Camera {
+    pub target: Point3d,
+    pub distance: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub roll: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            target: Point3d::default(),
+            distance: constants::CAMERA_DISTANCE,
+            yaw: 0_f64,
+            pitch: 0_f64,
+            roll: 0_f64,
+        }
+    }
+}
+
+impl Camera {
+    pub fn zoom(&self) -> f64 {
+        constants::CAMERA_DISTANCE / self.distance
+    }
+
+    pub fn orientation(&self) -> Quaternion {
+        Quaternion::from_euler(self.yaw, self.pitch, self.roll)
+    }
+
+    fn set_orientation(&mut self, orientation: &Quaternion) {
+        let (yaw, pitch, roll) = orientation.to_euler();
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        let target = Vec3d::new(-self.target.x, -self.target.y, -self.target.z);
+        let center = Vec3d::new(
+            (constants::WIDTH / 2) as f64,
+            (constants::HEIGHT / 2) as f64,
+            0_f64,
+        );
+        Matrix4::translation(&target) * Matrix4::from_trs(&center, &self.orientation(), self.zoom())
+    }
+
+    // point of the model shown at (x, y) of the screen in the plane of the target
+    pub fn unproject(&self, x: f64, y: f64) -> Option<Point3d> {
+        let mut p = Point3d::new(x, y, 0_f64);
+        self.matrix().inverse()?.apply_to_point(&mut p);
+        Some(p)
+    }
+
+    // rotation around the normalized vector of the screen after the current one
+    pub fn rotate(&mut self, angle: f64, axis: &Vec3d) {
+        let orientation = Quaternion::from_axis_angle(axis, angle) * self.orientation();
+        self.set_orientation(&orientation);
+    }
+
+    // rotation, in which `pivot` stays in place on the screen
+    pub fn rotate_around(&mut self, pivot: &Point3d, angle: f64, axis: &Vec3d) {
+        let offset = self
+            .orientation()
+            .rotate_vec(&Vec3d::from_pts(pivot, &self.target));
+        self.rotate(angle, axis);
+        let v = self.model_vector(&offset);
+        self.target = Point3d::new(pivot.x + v.x, pivot.y + v.y, pivot.z + v.z);
+    }
+
+    // scaling by `val`, in which `pivot` stays in place on the screen
+    pub fn zoom_around(&mut self, pivot: &Point3d, val: f64) {
+        self.target = Point3d::new(
+            pivot.x + (self.target.x - pivot.x) / val,
+            pivot.y + (self.target.y - pivot.y) / val,
+            pivot.z + (self.target.z - pivot.z) / val,
+        );
+        self.distance /= val;
+    }
+
+    // shift of the model on the screen (pixels)
+    pub fn pan(&mut self, v: &Vec3d) {
+        let v = self.model_vector(v);
+        let zoom = self.zoom();
+        self.target.x -= v.x / zoom;
+        self.target.y -= v.y / zoom;
+        self.target.z -= v.z / zoom;
+    }
+
+    // scaling by `val`, the point (x, y) of the screen stays in place
+    pub fn zoom_at(&mut self, val: f64, x: f64, y: f64) {
+        let before = self.unproject(x, y);
+        self.distance /= val;
+        if let (Some(before), Some(after)) = (before, self.unproject(x, y)) {
+            self.target.x += before.x - after.x;
+            self.target.y += before.y - after.y;
+            self.target.z += before.z - after.z;
+        }
+    }
+
+    pub fn set_preset(&mut self, preset: ViewPreset) {
+        let (yaw, pitch, roll) = preset.angles();
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+    }
+
+    // box is in the center and fills `FIT_PART` of the screen in any orientation
+    pub fn fit(&mut self, (min, max): (Point3d, Point3d)) {
+        self.target = Point3d::new(
+            (min.x + max.x) / 2_f64,
+            (min.y + max.y) / 2_f64,
+            (min.z + max.z) / 2_f64,
+        );
+        let radius = Vec3d::from_pts(&min, &max).len() / 2_f64;
+        if radius < f64::EPSILON {
+            return;
+        }
+        let size = constants::WIDTH.min(constants::HEIGHT) as f64;
+        self.distance = constants::CAMERA_DISTANCE * 2_f64 * radius / (size * constants::FIT_PART);
+    }
+
+    // camera between this one (`t` = 0) and `other` (`t` = 1): the orientation is
+    // interpolated by the shortest arc
+    pub fn interpolate(&self, other: &Camera, t: f64) -> Camera {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        let mut res = Camera {
+            target: Point3d::new(
+                lerp(self.target.x, other.target.x),
+                lerp(self.target.y, other.target.y),
+                lerp(self.target.z, other.target.z),
+            ),
+            distance: lerp(self.distance, other.distance),
+            ..*self
+        };
+        res.set_orientation(&self.orientation().slerp(&other.orientation(), t));
+        res
+    }
+
+    // vector of the screen in the model space
+    fn model_vector(&self, v: &Vec3d) -> Vec3d {
+        self.orientation().conjugate().rotate_vec(v)
+    }
+}
+
+pub fn bounding_box<'a, I>(points: I) -> Option<(Point3d, Point3d)>
+where
+    I: IntoIterator<Item = &'a Point3d>,
+{
+    points.into_iter().fold(None, |bounds, p| {
+        let (min, max) = bounds.unwrap_or((*p, *p));
+        Some((
+            Point3d::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+            Point3d::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+        ))
+    })
+}
@@ -1,5 +1,21 @@
 use super::prelude::*;
 
+// Place of the muscle on the carcass: `origin` is the distance from the elbow along
+// the first bone, `insertion` is the distance from the elbow along the second bone
+// (negative values are behind the joint, like the olecranon for the triceps).
+#[derive(Clone, Copy, Debug)]
+pub struct Attachment {
+    pub origin: f64,
+    pub insertion: f64,
+}
+
+impl Attachment {
+    pub fn new(origin: f64, insertion: f64) -> Self {
+        Self { origin, insertion }
+    }
+}
+
+#[derive(Clone)]
 pub struct Carcass {
     data: [[f64; 2]; 2],
     thickness: f64,
@@ -15,23 +31,177 @@ impl Carcass {
         }
     }
 
+    #[allow(dead_code)]
     pub fn data(&self) -> [[f64; 2]; 2] {
         self.data
     }
 
+    // attachment of the muscle, which length is stored in the carcass
+    pub fn default_attachment(&self) -> Attachment {
+        Attachment::new(self.data[0][1], self.data[1][0])
+    }
+
+    // angle in the elbow (PI - fully extended arm)
+    pub fn angle(&self) -> f64 {
+        angle_from_triangle(self.cur_len, self.data[0][1], self.data[1][0])
+    }
+
+    pub fn set_angle(&mut self, angle: f64) {
+        self.cur_len = self.muscle_len_at(&self.default_attachment(), angle);
+    }
+
+    // angle in the elbow, in which muscle with given attachment has given length
+    pub fn angle_for_len(&self, att: &Attachment, len: f64) -> f64 {
+        let cos = (att.origin * att.origin + att.insertion * att.insertion - len * len)
+            / (2_f64 * att.origin * att.insertion);
+        f64::acos(cos.clamp(-1_f64, 1_f64))
+    }
+
+    pub fn muscle_len(&self, att: &Attachment) -> f64 {
+        self.muscle_len_at(att, self.angle())
+    }
+
+    pub fn muscle_len_at(&self, att: &Attachment, angle: f64) -> f64 {
+        f64::sqrt(
+            att.origin * att.origin + att.insertion * att.insertion
+                - 2_f64 * att.origin * att.insertion * f64::cos(angle),
+        )
+    }
+
+    // Derivative of the muscle length by the angle in the elbow: positive for flexors
+    // (they become shorter when the arm bends), negative for extensors.
+    pub fn moment_arm(&self, att: &Attachment) -> f64 {
+        self.moment_arm_at(att, self.angle())
+    }
+
+    pub fn moment_arm_at(&self, att: &Attachment, angle: f64) -> f64 {
+        att.origin * att.insertion * f64::sin(angle) / self.muscle_len_at(att, angle)
+    }
+
+    // Lengths of the carcass muscle allowed by the joint, both bounds are excluded. On the
+    // lower bound the muscle is perpendicular to the shorter bone, on the upper one the arm
+    // is extended.
+    pub fn len_range(&self) -> (f64, f64) {
+        let (a, b) = (self.data[0][1], self.data[1][0]);
+        (
+            f64::sqrt(f64::abs(f64::powi(a, 2) - f64::powi(b, 2))),
+            a + b,
+        )
+    }
+
+    // Angles in the elbow allowed by `check_diff`. Bounds of `len_range` are excluded, so
+    // they are moved inside by `LENGTH_MARGIN` of the longest length.
+    pub fn angle_range(&self) -> (f64, f64) {
+        let (a, b) = (self.data[0][1], self.data[1][0]);
+        let (min, max) = self.len_range();
+        let margin = constants::LENGTH_MARGIN * max;
+        let (min, max) = (min + margin, max - margin);
+        (
+            angle_from_triangle(min, a, b),
+            angle_from_triangle(max, a, b),
+        )
+    }
+
+    // length of the second bone from the elbow to its distal end
+    pub fn forearm_len(&self) -> f64 {
+        self.data[1][0] + self.data[1][1]
+    }
+
+    // elbow and directions of both bones from it (muscle of the carcass lies on X axis
+    // from (0, 0) to (cur_len, 0))
+    fn joint(&self) -> (Point3d, Vec3d, Vec3d) {
+        let angle = angle_from_triangle(self.data[1][0], self.data[0][1], self.cur_len);
+        let joint = Point3d::new(
+            self.data[0][1] * f64::cos(angle),
+            self.data[0][1] * f64::sin(angle),
+            0_f64,
+        );
+        let mut dir1 = Vec3d::from_pts(&joint, &Point3d::default());
+        dir1.normalize();
+        let mut dir2 = Vec3d::from_pts(&joint, &Point3d::new(self.cur_len, 0_f64, 0_f64));
+        dir2.normalize();
+        (joint, dir1, dir2)
+    }
+
+    pub fn elbow(&self) -> Point3d {
+        self.joint().0
+    }
+
+    // bones from the elbow to their free ends: humerus and forearm
+    pub fn bones(&self) -> [(Point3d, Point3d); 2] {
+        let (joint, dir1, dir2) = self.joint();
+        let end = |dir: Vec3d, len: f64| {
+            Point3d::new(joint.x + dir.x * len, joint.y + dir.y * len, 0_f64)
+        };
+        [
+            (joint, end(dir1, self.data[0][0] + self.data[0][1])),
+            (joint, end(dir2, self.forearm_len())),
+        ]
+    }
+
+    pub fn attachment_points(&self, att: &Attachment) -> (Point3d, Point3d, Point3d) {
+        let (joint, dir1, dir2) = self.joint();
+        (
+            Point3d::new(
+                joint.x + dir1.x * att.origin,
+                joint.y + dir1.y * att.origin,
+                0_f64,
+            ),
+            Point3d::new(
+                joint.x + dir2.x * att.insertion,
+                joint.y + dir2.y * att.insertion,
+                0_f64,
+            ),
+            joint,
+        )
+    }
+
+    // matrix from the muscle space (muscle lies on X axis, elbow is on the positive Y
+    // side) to the carcass space
+    pub fn muscle_matrix(&self, att: &Attachment) -> Matrix4 {
+        let (origin, insertion, joint) = self.attachment_points(att);
+        let mut dir = Vec3d::from_pts(&origin, &insertion);
+        dir.normalize();
+        let to_joint = Vec3d::from_pts(&origin, &joint);
+        let side = if dir.x * to_joint.y - dir.y * to_joint.x < 0_f64 {
+            -1_f64
+        } else {
+            1_f64
+        };
+
+        Matrix4::from([
+            [dir.x, dir.y, 0_f64, 0_f64],
+            [-dir.y * side, dir.x * side, 0_f64, 0_f64],
+            [0_f64, 0_f64, 1_f64, 0_f64],
+            [origin.x, origin.y, 0_f64, 1_f64],
+        ])
+    }
+
+    // joint limit: the new length of the carcass muscle is inside `len_range`
     pub fn check_diff(&self, diff: f64) -> bool {
-        self.cur_len + diff < self.data[0][1] + self.data[1][0] // max
-            && self.cur_len + diff >
-            f64::sqrt(f64::abs(f64::powi(self.data[0][1], 2) - f64::powi(self.data[1][0], 2)))
-            // min
+        let (min, max) = self.len_range();
+        self.cur_len + diff > min && self.cur_len + diff < max
     }
 
-    #[allow(dead_code)]
-    pub fn bounder(&self) -> Box<dyn Fn(f64) -> f64> {
-        let a1 = -angle_from_triangle(self.data[1][0], self.data[0][1], self.cur_len);
-        let median = self.data[0][1] * f64::cos(a1);
-        let a2 = angle_from_triangle(self.data[0][1], self.data[1][0], self.cur_len);
-        let b = -self.cur_len * a2;
+    // The state is allowed on the bounds of `len_range` too (the extended arm of the
+    // config), `check_diff` keeps the next states inside.
+    pub fn in_joint_limit(&self) -> bool {
+        let (min, max) = self.len_range();
+        self.cur_len >= min && self.cur_len <= max
+    }
+
+    // length of the carcass muscle
+    pub fn len(&self) -> f64 {
+        self.cur_len
+    }
+
+    pub fn bounder(&self, att: &Attachment) -> Box<dyn Fn(f64) -> f64> {
+        let (origin, insertion) = (f64::abs(att.origin), f64::abs(att.insertion));
+        let len = self.muscle_len(att);
+        let a1 = -angle_from_triangle(insertion, origin, len);
+        let median = origin * f64::cos(a1);
+        let a2 = angle_from_triangle(origin, insertion, len);
+        let b = -len * a2;
         let thickness = self.thickness;
         Box::new(move |x| {
             if x <= median {
@@ -106,12 +276,12 @@ impl Carcass {
     ) {
         let (mut tube_points, mut tube_normals) = rotate_intersections(
             &[
-            Point3d::new(0_f64, self.thickness, 0_f64),
-            Point3d::new(len, self.thickness, 0_f64),
+                Point3d::new(0_f64, self.thickness, 0_f64),
+                Point3d::new(len, self.thickness, 0_f64),
             ],
             &[
-            Point3d::new(0_f64, 2_f64 * self.thickness, 0_f64),
-            Point3d::new(len, 2_f64 * self.thickness, 0_f64),
+                Point3d::new(0_f64, 2_f64 * self.thickness, 0_f64),
+                Point3d::new(len, 2_f64 * self.thickness, 0_f64),
             ],
             constants::CARCASS_STEP,
         );
@@ -1,17 +1,24 @@
+use std::ptr::{addr_of, addr_of_mut};
+
 use super::prelude::*;
 
 static mut Z_BUFFER: [[f64; constants::WIDTH]; constants::HEIGHT] =
     [[f64::MIN; constants::WIDTH]; constants::HEIGHT];
 static mut COLOR_BUFFER: [[u32; constants::WIDTH]; constants::HEIGHT] =
     [[constants::DEFAULT_COLOR; constants::WIDTH]; constants::HEIGHT];
+// object of every pixel (`NO_ID`, `LIGHT_ID`, `CARCASS_ID` or the muscle)
+static mut ID_BUFFER: [[u32; constants::WIDTH]; constants::HEIGHT] =
+    [[constants::NO_ID; constants::WIDTH]; constants::HEIGHT];
 
 // INPUT: points with normals, transformation matrix, light_source, color of input figure.
-// RESULT: flushes all visible parts of transformed figure in internal COLOR_BUFFER.
+// RESULT: flushes all visible parts of transformed figure in internal COLOR_BUFFER,
+// its visible pixels are marked by `id` in ID_BUFFER.
 pub unsafe fn transform_and_add(
     (points_groups, normals_groups): &(Vec<Vec<Point3d>>, Vec<Vec<Point3d>>),
     matrix: &Matrix4,
     light_source: Point3d,
     color: u32,
+    id: u32,
 ) {
     // for every triangulated group of input figure:
     for (points, normals) in points_groups.iter().zip(normals_groups.iter()) {
@@ -44,7 +51,7 @@ pub unsafe fn transform_and_add(
                     current_window[2].1,
                 ];
                 // add transformed triangle polygon to buffer
-                add_polygon(points, normals, &light_source, color);
+                add_polygon(points, normals, &light_source, color, id);
             }
         }
     }
@@ -55,6 +62,7 @@ unsafe fn add_polygon(
     mut normals: [Vec3d; 3],
     light_source: &Point3d,
     color: u32,
+    id: u32,
 ) {
     // cast Y coordinate to integer (coordinates of the screen are integers)
     let mut int_points = [
@@ -69,11 +77,11 @@ unsafe fn add_polygon(
     // divide triangle on 2 pairs of sections, which make up 2 triangles with
     // parallel to X axis edge
     let sections = divide_on_sections(int_points, brightnesses);
-    process_sections(sections, color);
+    process_sections(sections, color, id);
 }
 
 // application of Gouraud and Z-buffer algorithms for 2 processed triangles
-unsafe fn process_sections(mut sections: [Section; 4], color: u32) {
+unsafe fn process_sections(mut sections: [Section; 4], color: u32, id: u32) {
     for pair in sections.chunks_mut(2) {
         if pair[0].x_start > pair[1].x_start {
             continue;
@@ -105,6 +113,7 @@ unsafe fn process_sections(mut sections: [Section; 4], color: u32) {
             for x in (x_from..=x_to).filter(|&x| x < constants::WIDTH) {
                 if z > Z_BUFFER[y][x] {
                     Z_BUFFER[y][x] = z;
+                    ID_BUFFER[y][x] = id;
                     put_color(x, y, color, br);
                 }
 
@@ -122,7 +131,7 @@ unsafe fn process_sections(mut sections: [Section; 4], color: u32) {
 }
 
 pub unsafe fn flush(pb: Pixbuf) {
-    for (i, line) in COLOR_BUFFER.iter().enumerate() {
+    for (i, line) in (*addr_of!(COLOR_BUFFER)).iter().enumerate() {
         for (j, pixel) in line.iter().enumerate() {
             pb.put_pixel(
                 j as u32,
@@ -137,17 +146,79 @@ pub unsafe fn flush(pb: Pixbuf) {
 }
 
 pub unsafe fn clear_buffers() {
-    for line in Z_BUFFER.iter_mut() {
+    for line in (*addr_of_mut!(Z_BUFFER)).iter_mut() {
         for pixel in line.iter_mut() {
             *pixel = constants::MIN_Z;
         }
     }
 
-    for line in COLOR_BUFFER.iter_mut() {
+    for line in (*addr_of_mut!(COLOR_BUFFER)).iter_mut() {
         for color in line.iter_mut() {
             *color = constants::DEFAULT_COLOR;
         }
     }
+
+    for line in (*addr_of_mut!(ID_BUFFER)).iter_mut() {
+        for id in line.iter_mut() {
+            *id = constants::NO_ID;
+        }
+    }
+}
+
+// object at the pixel and its point on the screen (Z is taken from Z_BUFFER)
+pub unsafe fn object_at(x: f64, y: f64) -> Option<(u32, Point3d)> {
+    let (col, row) = (f64::round(x), f64::round(y));
+    if col < 0_f64 || row < 0_f64 {
+        return None;
+    }
+    let (col, row) = (col as usize, row as usize);
+    if col >= constants::WIDTH
+        || row >= constants::HEIGHT
+        || ID_BUFFER[row][col] == constants::NO_ID
+    {
+        return None;
+    }
+    Some((ID_BUFFER[row][col], Point3d::new(x, y, Z_BUFFER[row][col])))
+}
+
+// line over the model, it is drawn by points with step of one pixel
+pub unsafe fn draw_line(from: &Point3d, to: &Point3d, color: u32) {
+    let steps = f64::max(f64::abs(to.x - from.x), f64::abs(to.y - from.y)).ceil() as usize;
+    for i in 0..=steps {
+        let t = i as f64 / steps.max(1) as f64;
+        let (x, y) = (
+            f64::round(from.x + (to.x - from.x) * t),
+            f64::round(from.y + (to.y - from.y) * t),
+        );
+        if x >= 0_f64
+            && y >= 0_f64
+            && (x as usize) < constants::WIDTH
+            && (y as usize) < constants::HEIGHT
+        {
+            COLOR_BUFFER[y as usize][x as usize] = color;
+        }
+    }
+}
+
+// disc over the model, which is marked by `id`
+pub unsafe fn draw_disc(center: &Point3d, radius: f64, color: u32, id: u32) {
+    let (x_from, x_to) = (
+        f64::max(center.x - radius, 0_f64).floor() as usize,
+        f64::min(center.x + radius, (constants::WIDTH - 1) as f64).ceil() as usize,
+    );
+    let (y_from, y_to) = (
+        f64::max(center.y - radius, 0_f64).floor() as usize,
+        f64::min(center.y + radius, (constants::HEIGHT - 1) as f64).ceil() as usize,
+    );
+    for y in y_from..=y_to {
+        for x in x_from..=x_to {
+            let (dx, dy) = (x as f64 - center.x, y as f64 - center.y);
+            if dx * dx + dy * dy <= radius * radius {
+                COLOR_BUFFER[y][x] = color;
+                ID_BUFFER[y][x] = id;
+            }
+        }
+    }
 }
 
 unsafe fn put_color(x: usize, y: usize, color: u32, br: f64) {
@@ -155,7 +226,7 @@ unsafe fn put_color(x: usize, y: usize, color: u32, br: f64) {
         (color >> 24) as f64 * br,
         (color >> 16 & 0xFF) as f64 * br,
         (color >> 8 & 0xFF) as f64 * br,
-        (color & 0xFF) as u32,
+        color & 0xFF,
     );
     let (r, g, b) = (
         (f64::round(r) as u32) << 24,
@@ -185,8 +256,8 @@ fn find_brightnesses(
     light_source: &Point3d,
 ) -> [f64; 3] {
     let mut lsvs = Vec::with_capacity(3);
-    for i in 0..3 {
-        let mut lsv = Vec3d::from_pts(&points[i], light_source);
+    for point in points.iter() {
+        let mut lsv = Vec3d::from_pts(point, light_source);
         lsv.normalize();
         lsvs.push(lsv);
     }
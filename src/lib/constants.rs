@@ -1,4 +1,4 @@
-use super::prelude::Point3d;
+use super::prelude::{Command, Point3d};
 
 pub const WIDTH: usize = 800;
 pub const HEIGHT: usize = 600;
@@ -21,18 +21,115 @@ pub const SPHERE_PARTS: usize = 8;
 pub const ROTATE_VAL: f64 = 0.2;
 pub const MOVE_VAL: f64 = 20.0;
 pub const SCALE_VAL: f64 = 1.25;
+// radius of the sphere of the mouse rotation (pixels)
+pub const ARCBALL_RADIUS: f64 = 250.0;
+pub const ROTATE_BUTTON: u32 = 1;
+pub const PAN_BUTTON: u32 = 2;
+// cursor moved less than this distance (pixels) with the rotate button makes a click
+pub const PICK_TOLERANCE: f64 = 3.0;
+// cursor nearer to the ring of the picked node (pixels) drags it instead of rotation
+pub const RING_GRAB_DISTANCE: f64 = 6.0;
+// change of the grow mult per pixel of the drag
+pub const GM_DRAG_RATE: f64 = 0.01;
+// distance of the camera with the scale 1
+pub const CAMERA_DISTANCE: f64 = 1000.0;
+// part of the screen filled by the scene after zoom to fit
+pub const FIT_PART: f64 = 0.9;
+// cosine of the pitch, under which the roll is merged into the yaw
+pub const GIMBAL_EPS: f64 = 1e-9;
+// quaternions closer than this are interpolated linearly
+pub const SLERP_EPS: f64 = 1e-9;
+pub const SINGULAR_EPS: f64 = 1e-12;
+// frames of the camera movement to the chosen view
+pub const VIEW_TRANSITION_FRAMES: usize = 8;
 
 pub const ATOM_DIFF: f64 = 2.0;
 
+// relative error of the muscle volume
+pub const VOLUME_TOLERANCE: f64 = 1e-9;
+pub const SOLVER_ITERATIONS: usize = 100;
+
+pub const CONTACT_ITERATIONS: usize = 30;
+// part of the longest muscle of the carcass, by which bounds of `check_diff` are narrowed
+pub const LENGTH_MARGIN: f64 = 1e-6;
+// relative difference of the saved muscle length from the length given by its attachment
+pub const STATE_LEN_TOLERANCE: f64 = 1e-6;
+// ring is inflated at most 2^CONTACT_MAX_DOUBLINGS times
+pub const CONTACT_MAX_DOUBLINGS: usize = 16;
+
+// model lengths are in millimeters
+pub const UNITS_PER_METER: f64 = 1000.0;
+
+// N / mm^2
+pub const SPECIFIC_TENSION: f64 = 0.3;
+pub const HILL_FIBER_PART: f64 = 0.8;
+pub const HILL_MIN_FIBER_PART: f64 = 0.3;
+pub const HILL_MAX_VELOCITY: f64 = 10.0;
+pub const HILL_ACTIVATION_TIME: f64 = 0.01;
+pub const HILL_DEACTIVATION_TIME: f64 = 0.04;
+pub const HILL_FL_WIDTH: f64 = 0.45;
+pub const HILL_PE_SHAPE: f64 = 4.0;
+pub const HILL_PE_STRAIN: f64 = 0.6;
+pub const HILL_FV_SHAPE: f64 = 0.25;
+pub const HILL_FV_LENGTHENING_SHAPE: f64 = 0.08;
+pub const HILL_MAX_LENGTHENING_FORCE: f64 = 1.8;
+pub const HILL_FV_EPS: f64 = 1e-3;
+pub const HILL_TENDON_STRAIN: f64 = 0.033;
+pub const HILL_MIN_ACTIVATION: f64 = 0.01;
+pub const HILL_MIN_ACTIVE_FORCE: f64 = 0.1;
+pub const HILL_ITERATIONS: usize = 50;
+
+pub const GRAVITY: f64 = 9.81;
+pub const FOREARM_MASS: f64 = 1.5;
+pub const JOINT_DAMPING: f64 = 0.5;
+pub const SIM_DT: f64 = 0.0005;
+pub const SIM_FRAME_MS: u32 = 40;
+// states with the same name recorded within this time make one step of the history
+pub const HISTORY_MERGE_MS: u128 = 500;
+
+pub const SWEEP_SAMPLES: usize = 50;
+
+// Levenberg-Marquardt fitting of grow multipliers
+pub const FIT_ITERATIONS: usize = 200;
+pub const FIT_DAMPING_STEPS: usize = 10;
+pub const FIT_LAMBDA: f64 = 1e-3;
+pub const FIT_STEP: f64 = 1e-6;
+pub const FIT_TOLERANCE: f64 = 1e-10;
+
+// LaTeX table of the comparison with the real muscle (the docs are in Russian)
+pub const COMPARE_NODE_HEADING: &str = "Номер узла";
+pub const COMPARE_MAX_ERROR_HEADING: &str = "Макс. ошибка";
+pub const COMPARE_CAPTION: &str = "Радиусы узлов модели и ошибки относительно реальной мышцы";
+pub const COMPARE_LABEL: &str = "tab:compare";
+
 pub const MIN_Z: f64 = f64::MIN;
 pub const DEFAULT_COLOR: u32 = 0x1E1E1EFF;
 pub const MUSCLE_COLOR: u32 = 0xCC0000FF;
+pub const MUSCLE_COLORS: [u32; 4] = [MUSCLE_COLOR, 0xCC6600FF, 0x990033FF, 0xCC3366FF];
 pub const CARCASS_COLOR: u32 = 0xCCCCCCFF;
+// picked node, bone or light
+pub const HIGHLIGHT_COLOR: u32 = 0xFFD700FF;
+pub const LIGHT_COLOR: u32 = 0xFFFFAAFF;
+
+// objects of the ID buffer, muscles have ids from `FIRST_MUSCLE_ID` in their order
+pub const NO_ID: u32 = 0;
+pub const LIGHT_ID: u32 = 1;
+pub const CARCASS_ID: u32 = 2;
+pub const FIRST_MUSCLE_ID: u32 = 3;
+// light is shown by the disc, which stays on the screen
+pub const LIGHT_MARKER_RADIUS: f64 = 8.0;
+pub const LIGHT_MARKER_MARGIN: f64 = 20.0;
+// ring of the picked node
+pub const RING_SEGMENTS: usize = 64;
 
 pub const ZERO_BRIGHTNESS: f64 = 0.6;
 pub const BRIGHTNESS_RANGE: f64 = 0.4;
 
 pub const RELATIVE_CONF_PATH: [&str; 2] = ["config", "main.yaml"];
+// control panel, which is used without `--ui`
+pub const CONTROL_WINDOW_UI: &str = include_str!("../../config/control_window.glade");
+// period of the config check in the watch mode
+pub const WATCH_MS: u32 = 500;
 
 // this light vector must be normalized and it must direct to light source
 pub const LIGHT_SOURCE: Point3d = Point3d {
@@ -41,29 +138,42 @@ pub const LIGHT_SOURCE: Point3d = Point3d {
     z: -10000.0,
 };
 
-pub mod keys {
-    pub const H: u16 = 43;
-    pub const L: u16 = 46;
-    pub const J: u16 = 44;
-    pub const K: u16 = 45;
-    pub const F: u16 = 41;
-    pub const T: u16 = 28;
-
-    pub const A: u16 = 38;
-    pub const S: u16 = 39;
-    pub const D: u16 = 40;
-    pub const W: u16 = 25;
-    pub const Q: u16 = 24;
-    pub const E: u16 = 26;
-
-    pub const P: u16 = 33;
-    pub const M: u16 = 58;
-
-    pub const X: u16 = 53;
-    pub const V: u16 = 55;
-}
-
-pub const COMMANDS_BUTTONS_AMOUNT: usize = 16;
+// GDK_KEY_VoidSymbol, returned for unknown key names
+pub const VOID_KEYVAL: u32 = 0xffffff;
+// keybindings file is in the directory of the config
+pub const KEYS_FILE: &str = "keys.yaml";
+// position of the keybindings help over the model
+pub const HELP_OFFSET: i32 = 10;
+// used, if there is no keybindings file
+pub const DEFAULT_KEYBINDINGS: [(&str, Command); 25] = [
+    ("a", Command::MoveLeft),
+    ("d", Command::MoveRight),
+    ("w", Command::MoveUp),
+    ("s", Command::MoveDown),
+    ("e", Command::MoveBack),
+    ("q", Command::MoveFront),
+    ("h", Command::RotateLeft),
+    ("l", Command::RotateRight),
+    ("k", Command::RotateUp),
+    ("j", Command::RotateDown),
+    ("t", Command::RotateClockwise),
+    ("f", Command::RotateUnclockwise),
+    ("p", Command::ScaleUp),
+    ("m", Command::ScaleDown),
+    ("1", Command::ViewFront),
+    ("2", Command::ViewSide),
+    ("3", Command::ViewTop),
+    ("4", Command::ViewAnatomical),
+    ("z", Command::ZoomToFit),
+    ("v", Command::Lengthen),
+    ("x", Command::Shorten),
+    ("g", Command::Simulate),
+    ("Ctrl+z", Command::Undo),
+    ("Ctrl+y", Command::Redo),
+    ("F1", Command::Help),
+];
+
+pub const COMMANDS_BUTTONS_AMOUNT: usize = 19;
 pub const COMMANDS_BUTTONS: [&str; COMMANDS_BUTTONS_AMOUNT] = [
     "move_left",
     "move_up",
@@ -81,34 +191,41 @@ pub const COMMANDS_BUTTONS: [&str; COMMANDS_BUTTONS_AMOUNT] = [
     "scale_down",
     "lengthen",
     "shorten",
+    "simulate",
+    "undo",
+    "redo",
 ];
 
-pub const CMDS_BTNS_KEY_MAP: [u16; COMMANDS_BUTTONS_AMOUNT] = [
-    keys::A,
-    keys::W,
-    keys::E,
-    keys::D,
-    keys::S,
-    keys::Q,
-    keys::H,
-    keys::K,
-    keys::T,
-    keys::L,
-    keys::J,
-    keys::F,
-    keys::P,
-    keys::M,
-    keys::V,
-    keys::X,
+pub const CMDS_BTNS_MAP: [Command; COMMANDS_BUTTONS_AMOUNT] = [
+    Command::MoveLeft,
+    Command::MoveUp,
+    Command::MoveBack,
+    Command::MoveRight,
+    Command::MoveDown,
+    Command::MoveFront,
+    Command::RotateLeft,
+    Command::RotateUp,
+    Command::RotateClockwise,
+    Command::RotateRight,
+    Command::RotateDown,
+    Command::RotateUnclockwise,
+    Command::ScaleUp,
+    Command::ScaleDown,
+    Command::Lengthen,
+    Command::Shorten,
+    Command::Simulate,
+    Command::Undo,
+    Command::Redo,
 ];
 
-pub const INPUTS_AMOUNT: usize = 6;
+pub const INPUTS_AMOUNT: usize = 7;
 pub const POS_INPUT: usize = 0;
 pub const RAD_INPUT: usize = 1;
 pub const GM_INPUT: usize = 2;
 pub const X_INPUT: usize = 3;
 pub const Y_INPUT: usize = 4;
 pub const Z_INPUT: usize = 5;
+pub const FALLOFF_INPUT: usize = 6;
 pub const INPUTS_NAMES: [&str; INPUTS_AMOUNT] = [
     "pos_input",
     "rad_input",
@@ -116,6 +233,7 @@ pub const INPUTS_NAMES: [&str; INPUTS_AMOUNT] = [
     "x_input",
     "y_input",
     "z_input",
+    "falloff_input",
 ];
 
 pub const RBTNS_AMOUNT: usize = 10;
@@ -143,3 +261,41 @@ pub const RBTNS_NAMES: [&str; RBTNS_AMOUNT] = [
 ];
 
 pub const DELTA_VAL: f64 = 1.0;
+
+pub const MUSCLE_SELECT: &str = "muscle_select";
+
+pub const FORCE_MODEL_SELECT: &str = "force_model";
+pub const FORCE_MODELS: [&str; 2] = ["Hill", "Linear"];
+pub const ACTIVATION_INPUT: &str = "activation_input";
+pub const ACTIVATION_BTN: &str = "activation_btn";
+pub const MECHANICS_LABEL: &str = "mechanics_label";
+pub const EXPORT_SWEEP_BTN: &str = "export_sweep";
+pub const MASS_INPUT: &str = "mass_input";
+pub const LOAD_BTN: &str = "load_btn";
+pub const LOAD_LABEL: &str = "load_label";
+pub const DEFORMATION_SELECT: &str = "deformation_select";
+pub const DEFORMATION_MODELS: [&str; 3] = ["Grow mults", "Uniform scaling", "Per segment"];
+pub const NODES_INPUT: &str = "nodes_input";
+pub const RESAMPLE_BTN: &str = "resample_btn";
+pub const SMOOTH_FILTER_SELECT: &str = "smooth_filter";
+pub const SMOOTH_FILTERS: [&str; 2] = ["Gaussian", "Laplacian"];
+pub const SMOOTH_STRENGTH_INPUT: &str = "smooth_strength";
+pub const SMOOTH_GM_CHECK: &str = "smooth_gm";
+pub const SMOOTH_BTN: &str = "smooth_btn";
+pub const METRICS_LABEL: &str = "metrics_label";
+pub const RECORD_BTN: &str = "record_btn";
+// cross-sectional areas in one line of the metrics panel
+pub const AREAS_PER_LINE: usize = 4;
+pub const RANGE_SAMPLES_INPUT: &str = "range_samples";
+pub const EXPLORE_BTN: &str = "explore_btn";
+// samples of the admissible range exploration, if not set
+pub const RANGE_SAMPLES: usize = 91;
+pub const VIEW_SELECT: &str = "view_select";
+pub const FIT_BTN: &str = "fit_btn";
+pub const VIEW_NAME_INPUT: &str = "view_name";
+pub const SAVE_VIEW_BTN: &str = "save_view_btn";
+pub const PIVOT_SELECT: &str = "pivot_select";
+pub const PIVOTS: [&str; 4] = ["Scene center", "Elbow", "Node", "Point"];
+pub const PIVOT_INPUT: &str = "pivot_input";
+pub const PIVOT_BTN: &str = "pivot_btn";
+pub const HISTORY_LIST: &str = "history_list";
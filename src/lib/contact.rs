@@ -0,0 +1,216 @@
+use log::trace;
+
+use super::prelude::*;
+
+// Flattening of one ring of the muscle by a neighbour muscle: points of the ring `node`
+// which are further than `dist` from the axis in the direction `dir` (unit vector in
+// the ring plane, muscle space) are moved on the contact plane.
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    pub node: usize,
+    pub dir: Vec2d,
+    pub dist: f64,
+}
+
+// For every muscle finds rings which intersect volumes of other muscles. The contact
+// plane divides the overlap of two rings in halves.
+pub fn find_contacts(muscles: &[SceneMuscle], carcass: &Carcass) -> Vec<Vec<Contact>> {
+    let axes: Vec<(Point3d, Vec3d, Vec3d)> = muscles
+        .iter()
+        .map(|m| {
+            let matrix = carcass.muscle_matrix(m.attachment());
+            let mut origin = Point3d::default();
+            let mut x_end = Point3d::new(1_f64, 0_f64, 0_f64);
+            let mut y_end = Point3d::new(0_f64, 1_f64, 0_f64);
+            matrix.apply_to_point(&mut origin);
+            matrix.apply_to_point(&mut x_end);
+            matrix.apply_to_point(&mut y_end);
+            (
+                origin,
+                Vec3d::from_pts(&origin, &x_end),
+                Vec3d::from_pts(&origin, &y_end),
+            )
+        })
+        .collect();
+
+    let mut contacts = vec![Vec::new(); muscles.len()];
+    for (a, ma) in muscles.iter().enumerate() {
+        let muscle_a = ma.muscle().lock().unwrap();
+        let (origin_a, dir_a, y_a) = axes[a];
+
+        for (b, mb) in muscles.iter().enumerate().filter(|&(b, _)| b != a) {
+            let muscle_b = mb.muscle().lock().unwrap();
+            let (origin_b, dir_b, _) = axes[b];
+
+            for (node, &rad_a) in muscle_a.radiuses().iter().enumerate() {
+                let shift = muscle_a.dx() * node as f64;
+                let center = Point3d::new(
+                    origin_a.x + dir_a.x * shift,
+                    origin_a.y + dir_a.y * shift,
+                    origin_a.z + dir_a.z * shift,
+                );
+                let to_center = Vec3d::from_pts(&origin_b, &center);
+                let t = to_center.scalar_mul(&dir_b).max(0_f64).min(muscle_b.len());
+                let closest = Point3d::new(
+                    origin_b.x + dir_b.x * t,
+                    origin_b.y + dir_b.y * t,
+                    origin_b.z + dir_b.z * t,
+                );
+
+                // direction to the other axis in the plane of the ring
+                let mut to_b = Vec3d::from_pts(&center, &closest);
+                let along = to_b.scalar_mul(&dir_a);
+                to_b.x -= dir_a.x * along;
+                to_b.y -= dir_a.y * along;
+                to_b.z -= dir_a.z * along;
+                let dist = to_b.len();
+
+                let depth = rad_a + muscle_b.radius_at(t) - dist;
+                if depth <= 0_f64 || relative_eq!(dist, 0_f64) {
+                    continue;
+                }
+                to_b.normalize();
+                trace!(
+                    "Contact {} - {}: node {}, depth {}",
+                    ma.name(),
+                    mb.name(),
+                    node,
+                    depth
+                );
+                contacts[a].push(Contact {
+                    node,
+                    dir: Vec2d::new(to_b.scalar_mul(&y_a), to_b.z),
+                    dist: (rad_a - depth / 2_f64).max(0_f64),
+                });
+            }
+        }
+    }
+
+    contacts
+}
+
+// area of the circle segment cut by the chord on the distance `dist` from the center
+fn segment_area(rad: f64, dist: f64) -> f64 {
+    if dist >= rad {
+        return 0_f64;
+    }
+    rad * rad * f64::acos(dist / rad) - dist * f64::sqrt(rad * rad - dist * dist)
+}
+
+// radius of the drawn ring which keeps area of the initial ring after all cuts
+fn display_ring_radius(rad: f64, dists: &[f64]) -> f64 {
+    let area = |r: f64| {
+        std::f64::consts::PI * r * r - dists.iter().map(|&d| segment_area(r, d)).sum::<f64>()
+    };
+    let target = std::f64::consts::PI * rad * rad;
+
+    let (mut low, mut high) = (rad, 2_f64 * rad);
+    for _ in 0..constants::CONTACT_MAX_DOUBLINGS {
+        if area(high) >= target {
+            break;
+        }
+        high *= 2_f64;
+    }
+    // cuts take the ring of any size (opposite contacts near the axis)
+    if area(high) < target {
+        return rad;
+    }
+    for _ in 0..constants::CONTACT_ITERATIONS {
+        let mid = (low + high) / 2_f64;
+        if area(mid) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    (low + high) / 2_f64
+}
+
+// Moves ring points of the muscle (muscle space) on contact planes. Only the drawn
+// points are changed: radiuses of the muscle stay, so its volume and metrics don't see
+// the contact. Rings are inflated before flattening, so the area of every drawn
+// cross-section is the area of the circle with its radius.
+pub fn flatten_display_rings(
+    points: &mut [Vec<Point3d>],
+    normals: &mut [Vec<Point3d>],
+    radiuses: &[f64],
+    contacts: &[Contact],
+) {
+    for (node, &rad) in radiuses.iter().enumerate() {
+        let node_contacts: Vec<&Contact> = contacts.iter().filter(|c| c.node == node).collect();
+        if node_contacts.is_empty() || relative_eq!(rad, 0_f64) {
+            continue;
+        }
+        let dists: Vec<f64> = node_contacts.iter().map(|c| c.dist).collect();
+        let mult = display_ring_radius(rad, &dists) / rad;
+
+        // ring of the node is the beginning of the connector `node` (even points)
+        // and the end of the connector `node - 1` (odd points)
+        let mut rings = Vec::with_capacity(2);
+        if node + 1 < radiuses.len() {
+            rings.push((node, 0));
+        }
+        if node > 0 {
+            rings.push((node - 1, 1));
+        }
+
+        for (group, skip) in rings {
+            for (p, n) in points[group]
+                .iter_mut()
+                .skip(skip)
+                .step_by(2)
+                .zip(normals[group].iter_mut().skip(skip).step_by(2))
+            {
+                let (old_y, old_z) = (p.y, p.z);
+                p.y *= mult;
+                p.z *= mult;
+
+                let mut flattened = None;
+                for c in node_contacts.iter() {
+                    let proj = p.y * c.dir.x + p.z * c.dir.y;
+                    if proj > c.dist {
+                        p.y -= (proj - c.dist) * c.dir.x;
+                        p.z -= (proj - c.dist) * c.dir.y;
+                        flattened = Some(c.dir);
+                    }
+                }
+
+                match flattened {
+                    Some(dir) => {
+                        n.x = p.x;
+                        n.y = p.y + dir.x;
+                        n.z = p.z + dir.y;
+                    }
+                    None => {
+                        n.y += p.y - old_y;
+                        n.z += p.z - old_z;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_ring_keeps_area() {
+        let rad = 10_f64;
+        let dists = [6_f64, 7_f64];
+        let r = display_ring_radius(rad, &dists);
+        let area =
+            std::f64::consts::PI * r * r - dists.iter().map(|&d| segment_area(r, d)).sum::<f64>();
+        assert!(r > rad);
+        assert_relative_eq!(area, std::f64::consts::PI * rad * rad, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn opposite_contacts_on_axis() {
+        let r = display_ring_radius(10_f64, &[0_f64, 0_f64]);
+        assert!(r.is_finite());
+        assert_relative_eq!(r, 10_f64);
+    }
+}
@@ -1,94 +1,561 @@
-use log::{trace, debug, info};
+use log::{debug, info, trace};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use termion::{color, style};
 
 use super::prelude::*;
-use keys::*;
 
 enum Operation {
     Scale,
+    // scaling around the point of the screen
+    ScaleAt(f64, f64),
     Rotate(Axis),
+    // rotation around the normalized vector
+    RotateAround(Vec3d),
     Move(Axis),
 }
 
+// change of the picked node by the mouse, values are taken from the start of the drag
+#[derive(Clone)]
+struct NodeDrag {
+    muscle: usize,
+    node: usize,
+    // center of the node on the screen and the distance of the cursor from it
+    center: Point3d,
+    start: f64,
+    rad: f64,
+    gm: f64,
+    // grow mult is changed instead of the radius
+    grow_mult: bool,
+}
+
+// points and normals of the parts of a muscle or the carcass
+type PointsAndNormals = (Vec<Vec<Point3d>>, Vec<Vec<Point3d>>);
+
 #[derive(Clone)]
 pub struct Controller {
-    height: usize,
-    width: usize,
     pb: Pixbuf,
 
-    muscle: Arc<Mutex<Muscle>>,
+    // config of the scene, which is saved in the session
+    config: Config,
+    muscles: Vec<SceneMuscle>,
+    selected: usize,
     carcass: Arc<Mutex<Carcass>>,
-    cached_muscle: Option<(Vec<Vec<Point3d>>, Vec<Vec<Point3d>>)>,
-    cached_carcass: Option<(Vec<Vec<Point3d>>, Vec<Vec<Point3d>>)>,
+    cached_muscles: Option<Vec<PointsAndNormals>>,
+    cached_carcass: Option<PointsAndNormals>,
 
-    matrix: Matrix4,
+    sim_config: SimulationConfig,
+    simulation: Option<Simulation>,
+    activations: Vec<f64>,
+    force_model: ForceModel,
+    load: Option<Load>,
+
+    // volumes of the muscles in the initial state
+    initial_volumes: Vec<f64>,
+    recorder: Option<MetricsRecorder>,
+
+    camera: Camera,
+    // named cameras saved by the user
+    views: Vec<(String, Camera)>,
+    // movement of the camera to the chosen view: (from, to, frame)
+    transition: Option<(Camera, Camera, usize)>,
+    pivot: Pivot,
+    // highlighted object
+    picked: Option<Pick>,
+    node_drag: Option<NodeDrag>,
+    // name of the change by the mouse drag, which is recorded on the release
+    drag_change: Option<&'static str>,
     light_source: Point3d,
+    history: History,
 }
 
 impl Controller {
-    pub fn new(pb: Pixbuf, muscle: Arc<Mutex<Muscle>>, carcass: Arc<Mutex<Carcass>>) -> Self {
-        let mut matrix = Matrix4::identity();
-        matrix.mov((constants::WIDTH / 2) as f64, Axis::X);
-        matrix.mov((constants::HEIGHT / 2) as f64, Axis::Y);
-
-        Self {
-            height: constants::HEIGHT,
-            width: constants::WIDTH,
+    pub fn new(
+        pb: Pixbuf,
+        muscles: Vec<SceneMuscle>,
+        carcass: Arc<Mutex<Carcass>>,
+        config: Config,
+    ) -> Self {
+        let mut controller = Self {
             pb,
             carcass,
-            muscle,
-            matrix,
-            cached_muscle: None,
+            selected: 0,
+            camera: Camera::default(),
+            views: Vec::new(),
+            transition: None,
+            pivot: Pivot::SceneCenter,
+            picked: None,
+            node_drag: None,
+            drag_change: None,
+            cached_muscles: None,
             cached_carcass: None,
+            sim_config: config.simulation,
+            config,
+            simulation: None,
+            activations: vec![0_f64; muscles.len()],
+            force_model: ForceModel::Hill,
+            load: None,
+            initial_volumes: muscles
+                .iter()
+                .map(|m| m.muscle().lock().unwrap().volume())
+                .collect(),
+            recorder: None,
+            muscles,
             light_source: constants::LIGHT_SOURCE,
+            history: History::default(),
+        };
+        controller.record("initial state");
+        controller
+    }
+
+    // controller with the state of the session and the new history
+    pub fn from_session(pb: Pixbuf, session: &Session) -> Result<Self, String> {
+        let (muscles, carcass) = session.build_scene()?;
+        let mut controller = Self::new(
+            pb,
+            muscles,
+            Arc::new(Mutex::new(carcass)),
+            session.config.clone(),
+        );
+        controller.selected = session.selected;
+        controller.activations = session.activations.clone();
+        controller.force_model = session.force_model;
+        controller.load = session.load;
+        controller.camera = session.camera;
+        controller.views = session
+            .views
+            .iter()
+            .map(|view| (view.name.clone(), view.camera))
+            .collect();
+        controller.pivot = session.pivot;
+        controller.light_source = session.light_source;
+        controller.history = History::default();
+        controller.record("open session");
+        Ok(controller)
+    }
+
+    // Scene is built again by the changed config, the view and the selected muscle (by
+    // name) are kept. History starts again, because the muscles can be other.
+    pub fn reload_config(&mut self, config: Config) -> Result<(), String> {
+        let (muscles, carcass) = build_scene(config.clone())?;
+        let mut controller = Self::new(
+            self.pb.clone(),
+            muscles,
+            Arc::new(Mutex::new(carcass)),
+            config,
+        );
+        let name = self.muscles[self.selected].name().to_string();
+        if controller.select_muscle(&name).is_err() {
+            controller.selected = 0;
+        }
+        controller.camera = self.transition.map_or(self.camera, |(_, to, _)| to);
+        controller.views = self.views.clone();
+        // node can be removed
+        if !matches!(self.pivot, Pivot::Node(..)) {
+            controller.pivot = self.pivot;
+        }
+        controller.light_source = self.light_source;
+        controller.history = History::default();
+        controller.record("reload config");
+        controller.update_pixbuf();
+        *self = controller;
+        Ok(())
+    }
+
+    pub fn session(&self) -> Session {
+        let carcass = self.carcass.lock().unwrap();
+        Session {
+            selected: self.selected,
+            activations: self.activations.clone(),
+            force_model: self.force_model,
+            load: self.load,
+            camera: self.transition.map_or(self.camera, |(_, to, _)| to),
+            views: self
+                .views
+                .iter()
+                .map(|(name, camera)| SavedView {
+                    name: name.clone(),
+                    camera: *camera,
+                })
+                .collect(),
+            pivot: self.pivot,
+            light_source: self.light_source,
+            ..Session::new(self.config.clone(), &self.muscles, &carcass)
+        }
+    }
+
+    pub fn muscle_names(&self) -> Vec<String> {
+        self.muscles.iter().map(|m| m.name().to_string()).collect()
+    }
+
+    pub fn select_muscle(&mut self, name: &str) -> Result<(), String> {
+        match self.muscles.iter().position(|m| m.name() == name) {
+            Some(index) => {
+                self.selected = index;
+                Ok(())
+            }
+            None => Err(format!("No muscle with name {}!", name)),
         }
     }
 
+    pub fn deformation_model(&self) -> DeformationKind {
+        self.muscles[self.selected].muscle().lock().unwrap().model()
+    }
+
+    // model is used for the next deformations of the selected muscle
+    pub fn set_deformation_model(&mut self, model: DeformationKind) {
+        self.muscles[self.selected]
+            .muscle()
+            .lock()
+            .unwrap()
+            .set_model(model);
+    }
+
+    // activation of the selected muscle for static mechanics
+    pub fn set_activation(&mut self, activation: f64) -> Result<(), String> {
+        if !(0_f64..=1_f64).contains(&activation) {
+            return Err(format!("Activation must be in [0, 1]: {}", activation));
+        }
+        self.activations[self.selected] = activation;
+        Ok(())
+    }
+
+    pub fn force_model(&self) -> ForceModel {
+        self.force_model
+    }
+
+    pub fn set_force_model(&mut self, model: ForceModel) {
+        self.force_model = model;
+    }
+
+    // Mechanics of the current state: forces of the running simulation or static forces
+    // with activations set by the user.
+    pub fn mechanics(&self) -> JointMechanics {
+        let carcass = self.carcass.lock().unwrap();
+        let angle = carcass.angle();
+        let forces = match &self.simulation {
+            Some(simulation) => simulation.states().iter().map(|s| s.tendon_force).collect(),
+            None => static_forces(
+                &self.muscles,
+                &carcass,
+                angle,
+                &self.activations,
+                self.force_model,
+            ),
+        };
+        joint_mechanics(&self.muscles, &carcass, angle, &forces)
+    }
+
+    // load in the hand held by the selected muscle (zero mass removes the load)
+    pub fn set_load(&mut self, mass: f64) -> Result<(), String> {
+        if mass < 0_f64 {
+            return Err(format!("Mass can't be negative: {}", mass));
+        }
+        self.load = if relative_eq!(mass, 0_f64) {
+            None
+        } else {
+            Some(Load {
+                mass,
+                muscle: self.selected,
+            })
+        };
+        self.cached_muscles = None;
+        Ok(())
+    }
+
+    pub fn load_result(&self) -> Option<(Load, LoadResult)> {
+        let load = self.load?;
+        let carcass = self.carcass.lock().unwrap();
+        Some((
+            load,
+            hold(
+                &load,
+                &self.muscles,
+                &carcass,
+                &self.activations,
+                self.force_model,
+                self.sim_config.forearm_mass,
+            ),
+        ))
+    }
+
+    // metrics of the selected muscle
+    pub fn metrics(&self) -> MuscleMetrics {
+        let angle = self.carcass.lock().unwrap().angle();
+        let muscle = self.muscles[self.selected].muscle().lock().unwrap();
+        MuscleMetrics::new(&muscle, angle, self.initial_volumes[self.selected])
+    }
+
+    // metrics of the selected muscle are recorded from now on every deformation
+    pub fn start_recording(&mut self) {
+        let mut recorder = MetricsRecorder::new(self.selected);
+        recorder.record(self.metrics());
+        self.recorder = Some(recorder);
+    }
+
+    pub fn stop_recording(&mut self) -> Option<MetricsRecorder> {
+        self.recorder.take()
+    }
+
+    pub fn explore_range(&self, samples: usize) -> AdmissibleRange {
+        let carcass = self.carcass.lock().unwrap();
+        explore(&self.muscles, &carcass, samples)
+    }
+
+    pub fn export_sweep(&self, path: &std::path::Path) -> Result<(), String> {
+        let rows = {
+            let carcass = self.carcass.lock().unwrap();
+            sweep(
+                &self.muscles,
+                &carcass,
+                &self.activations,
+                self.force_model,
+                constants::SWEEP_SAMPLES,
+            )
+        };
+        write_sweep_csv(path, &self.muscle_names(), &rows)
+            .map_err(|err| format!("Can't export sweep!\n{}", err))
+    }
+
     pub fn restruct_muscle(&mut self, mo: MuscleOperation) -> Result<(), String> {
-        let mut muscle = self.muscle.lock().unwrap();
-        let bounder = self.carcass.lock().unwrap().bounder();
-        muscle.restruct(mo)?;
-        self.cached_muscle = Some(muscle.bget_points_and_normals(bounder));
+        let name = mo.name();
+        self.muscles[self.selected]
+            .muscle()
+            .lock()
+            .unwrap()
+            .restruct(mo)?;
+        self.cached_muscles = None;
+        self.record(name);
         Ok(())
     }
 
     pub fn get_node(&self, pos: usize) -> Result<(f64, f64), String> {
-        self.muscle.lock().unwrap().get_node(pos)
+        self.muscles[self.selected]
+            .muscle()
+            .lock()
+            .unwrap()
+            .get_node(pos)
     }
 
-    fn deform(&mut self, diff: f64) {
+    // all muscles follow the joint: lengths are taken from their attachments
+    fn deform(&mut self, diff: f64) -> bool {
         let mut carcass = self.carcass.lock().unwrap();
-        if carcass.check_diff(diff) {
-            let mut muscle = self.muscle.lock().unwrap();
-            carcass.deform(diff);
-            let bounder = carcass.bounder();
-            muscle.deform(diff);
-            self.cached_muscle = Some(muscle.bget_points_and_normals(bounder));
-            self.cached_carcass = Some(carcass.get_points_and_normals());
-            let data = carcass.data();
-            trace!("Updated radiuses: {:?}\nangle: {}", muscle.radiuses(),
-            180_f64 / std::f64::consts::PI * angle_from_triangle(muscle.len(), data[0][1], data[1][0]));
+        if !carcass.check_diff(diff) {
+            return false;
+        }
+        carcass.deform(diff);
+
+        // muscles are deformed all or none
+        let mut deformed = Vec::with_capacity(self.muscles.len());
+        for m in self.muscles.iter() {
+            let mut muscle = m.muscle().lock().unwrap().clone();
+            match muscle.deform(carcass.muscle_len(m.attachment()) - muscle.len()) {
+                Ok(report) => trace!(
+                    "Updated radiuses of {}: {:?}; volume error: {:e} ({} iterations)",
+                    m.name(),
+                    muscle.radiuses(),
+                    report.volume_error,
+                    report.iterations
+                ),
+                Err(err) => {
+                    debug!("Can't deform {}: {}", m.name(), err);
+                    carcass.deform(-diff);
+                    return false;
+                }
+            }
+            deformed.push(muscle);
+        }
+
+        for (m, muscle) in self.muscles.iter().zip(deformed) {
+            *m.muscle().lock().unwrap() = muscle;
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            let i = recorder.muscle();
+            let muscle = self.muscles[i].muscle().lock().unwrap();
+            recorder.record(MuscleMetrics::new(
+                &muscle,
+                carcass.angle(),
+                self.initial_volumes[i],
+            ));
+        }
+        trace!(
+            "angle: {}",
+            180_f64 / std::f64::consts::PI * carcass.angle()
+        );
+        self.cached_muscles = None;
+        self.cached_carcass = Some(carcass.get_points_and_normals());
+        true
+    }
+
+    fn set_angle(&mut self, angle: f64) -> bool {
+        let diff = {
+            let carcass = self.carcass.lock().unwrap();
+            let att = carcass.default_attachment();
+            carcass.muscle_len_at(&att, angle) - carcass.muscle_len(&att)
+        };
+        self.deform(diff)
+    }
+
+    pub fn toggle_simulation(&mut self) {
+        self.simulation = match self.simulation {
+            Some(_) => {
+                self.record("simulation");
+                None
+            }
+            None => {
+                let carcass = self.carcass.lock().unwrap();
+                Some(Simulation::new(self.sim_config, &self.muscles, &carcass))
+            }
+        };
+    }
+
+    // Integrates the simulation for the time of one frame; returns false if there is
+    // no running simulation.
+    pub fn simulation_frame(&mut self) -> bool {
+        let mut simulation = match self.simulation.take() {
+            Some(simulation) => simulation,
+            None => return false,
+        };
+        let steps = (constants::SIM_FRAME_MS as f64 / 1000_f64 / self.sim_config.dt) as usize;
+
+        for _ in 0..steps {
+            let angle = {
+                let carcass = self.carcass.lock().unwrap();
+                simulation.step(&self.muscles, &carcass)
+            };
+            if !self.set_angle(angle) {
+                simulation.stop_joint();
+            }
         }
+        trace!(
+            "Simulation time: {}; activations: {:?}",
+            simulation.time(),
+            simulation
+                .states()
+                .iter()
+                .map(|s| s.activation)
+                .collect::<Vec<_>>()
+        );
+
+        self.simulation = Some(simulation);
+        self.update_pixbuf();
+        true
     }
 
     pub fn move_light_source(&mut self, p: Point3d) {
         self.light_source = p;
+        self.record("move light");
     }
 
-    pub fn update_pixbuf(&mut self) {
-        if let None = self.cached_muscle {
-            let muscle = self.muscle.lock().unwrap();
-            let bounder = self.carcass.lock().unwrap().bounder();
-            self.cached_muscle = Some(muscle.bget_points_and_normals(bounder));
+    pub fn light_source(&self) -> Point3d {
+        self.light_source
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            muscles: self
+                .muscles
+                .iter()
+                .map(|m| m.muscle().lock().unwrap().clone())
+                .collect(),
+            carcass: self.carcass.lock().unwrap().clone(),
+            selected: self.selected,
+            activations: self.activations.clone(),
+            force_model: self.force_model,
+            load: self.load,
+            // transition ends at the chosen view
+            camera: self.transition.map_or(self.camera, |(_, to, _)| to),
+            pivot: self.pivot,
+            light_source: self.light_source,
+        }
+    }
+
+    // current state is added to the history after the change
+    fn record(&mut self, name: &str) {
+        let snapshot = self.snapshot();
+        self.history.record(name, snapshot);
+    }
+
+    // running simulation and drag are stopped, the state is replaced
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.simulation = None;
+        self.node_drag = None;
+        self.drag_change = None;
+        self.transition = None;
+        for (m, muscle) in self.muscles.iter().zip(snapshot.muscles) {
+            *m.muscle().lock().unwrap() = muscle;
+        }
+        *self.carcass.lock().unwrap() = snapshot.carcass;
+        self.selected = snapshot.selected;
+        self.activations = snapshot.activations;
+        self.force_model = snapshot.force_model;
+        self.load = snapshot.load;
+        self.camera = snapshot.camera;
+        self.pivot = snapshot.pivot;
+        self.light_source = snapshot.light_source;
+        self.cached_muscles = None;
+        self.cached_carcass = None;
+        self.update_pixbuf();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.history.undo() {
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.history.redo() {
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn jump_to(&mut self, index: usize) {
+        if let Some(snapshot) = self.history.jump(index) {
+            self.restore(snapshot);
+        }
+    }
+
+    // names of the states and the index of the current one
+    pub fn history(&self) -> (Vec<String>, usize) {
+        (self.history.names(), self.history.current())
+    }
+
+    pub fn history_revision(&self) -> usize {
+        self.history.revision()
+    }
+
+    // points of the muscles and the carcass in the model space
+    fn update_cache(&mut self) {
+        if self.cached_muscles.is_none() {
+            // holding muscle is shown with the belly shortened by the load
+            let loaded = self.load_result();
+            let carcass = self.carcass.lock().unwrap();
+            let contacts = find_contacts(&self.muscles, &carcass);
+            self.cached_muscles = Some(
+                self.muscles
+                    .iter()
+                    .enumerate()
+                    .zip(contacts.iter())
+                    .map(|((i, m), c)| match &loaded {
+                        Some((load, result)) if load.muscle == i => {
+                            m.get_points_and_normals_of(&result.muscle, &carcass, c)
+                        }
+                        _ => m.get_points_and_normals(&carcass, c),
+                    })
+                    .collect(),
+            );
         }
 
-        if let None = self.cached_carcass {
+        if self.cached_carcass.is_none() {
             let carcass = self.carcass.lock().unwrap();
             self.cached_carcass = Some(carcass.get_points_and_normals());
         }
+    }
 
+    pub fn update_pixbuf(&mut self) {
+        self.update_cache();
+        let matrix = self.camera.matrix();
         unsafe {
             debug!(
                 "{}=================== DRAW ROUTINES ======================",
@@ -98,27 +565,32 @@ impl Controller {
             clear_buffers();
             debug!("Clear buffers: {} ms", time.elapsed().as_millis());
             let time = Instant::now();
-            transform_and_add(
-                self.cached_muscle.as_ref().unwrap(),
-                &self.matrix,
-                self.light_source,
-                constants::MUSCLE_COLOR,
-            );
+            for (i, (m, cached)) in self
+                .muscles
+                .iter()
+                .zip(self.cached_muscles.as_ref().unwrap().iter())
+                .enumerate()
+            {
+                let id = constants::FIRST_MUSCLE_ID + i as u32;
+                transform_and_add(cached, &matrix, self.light_source, m.color(), id);
+            }
             debug!(
-                "Transform and add muscle: {} ms",
+                "Transform and add muscles: {} ms",
                 time.elapsed().as_millis()
             );
             let time = Instant::now();
             transform_and_add(
                 self.cached_carcass.as_ref().unwrap(),
-                &self.matrix,
+                &matrix,
                 self.light_source,
                 constants::CARCASS_COLOR,
+                constants::CARCASS_ID,
             );
             debug!(
                 "Transform and add carcass: {} ms",
                 time.elapsed().as_millis()
             );
+            self.draw_overlay(&matrix);
 
             let time = Instant::now();
             flush(self.pb.clone());
@@ -130,46 +602,396 @@ impl Controller {
         }
 
         let mut zp = Point3d::new(0.0, 0.0, 0.0);
-        self.matrix.apply_to_point(&mut zp);
+        matrix.apply_to_point(&mut zp);
         info!("POSITION: {}", zp);
     }
 
-    fn update_matrix(&mut self, operation: Operation, val: f64) {
+    // light marker and the outline of the picked object over the model
+    unsafe fn draw_overlay(&self, matrix: &Matrix4) {
+        let light_color = match self.picked {
+            Some(Pick::Light) => constants::HIGHLIGHT_COLOR,
+            _ => constants::LIGHT_COLOR,
+        };
+        draw_disc(
+            &light_marker(&self.light_source),
+            constants::LIGHT_MARKER_RADIUS,
+            light_color,
+            constants::LIGHT_ID,
+        );
+
+        let carcass = self.carcass.lock().unwrap();
+        let mut outline = match self.picked {
+            Some(Pick::Node(muscle, node)) => self.muscles[muscle].node_ring(&carcass, node),
+            Some(Pick::Bone(bone)) => {
+                let (from, to) = carcass.bones()[bone];
+                vec![from, to]
+            }
+            _ => Vec::new(),
+        };
+        for p in outline.iter_mut() {
+            matrix.apply_to_point(p);
+        }
+        for pair in outline.windows(2) {
+            draw_line(&pair[0], &pair[1], constants::HIGHLIGHT_COLOR);
+        }
+    }
+
+    // Picks the object under the point of the screen and highlights it. The picked
+    // muscle becomes selected.
+    pub fn pick(&mut self, x: f64, y: f64) -> Option<Pick> {
+        let object = unsafe { object_at(x, y) };
+        self.picked = object.and_then(|(id, mut p)| {
+            if id == constants::LIGHT_ID {
+                return Some(Pick::Light);
+            }
+            // point of the surface in the carcass space
+            self.camera.matrix().inverse()?.apply_to_point(&mut p);
+            let carcass = self.carcass.lock().unwrap();
+            if id == constants::CARCASS_ID {
+                return nearest_segment(&p, &carcass.bones()).map(Pick::Bone);
+            }
+            // unknown id below the muscle ids isn't picked
+            let muscle = id.checked_sub(constants::FIRST_MUSCLE_ID)? as usize;
+            let node = self.muscles.get(muscle)?.nearest_node(&carcass, &p);
+            Some(Pick::Node(muscle, node))
+        });
+        if let Some(Pick::Node(muscle, _)) = self.picked {
+            self.selected = muscle;
+        }
+        if let Some(pick) = self.picked {
+            info!("PICKED: {}", pick.describe(&self.muscle_names()));
+        }
+        self.update_pixbuf();
+        self.picked
+    }
+
+    // Starts the drag of the picked node, if the point of the screen is near its ring;
+    // returns false otherwise.
+    pub fn grab_ring(&mut self, x: f64, y: f64, grow_mult: bool) -> bool {
+        let (muscle, node) = match self.picked {
+            Some(Pick::Node(muscle, node)) => (muscle, node),
+            _ => return false,
+        };
+        let matrix = self.camera.matrix();
+        let to_screen = |mut p: Point3d| {
+            matrix.apply_to_point(&mut p);
+            Point3d::new(p.x, p.y, 0_f64)
+        };
+        let carcass = self.carcass.lock().unwrap();
+        let m = &self.muscles[muscle];
+        // view is rotated instead
+        if m.muscle().lock().unwrap().keyframed() {
+            debug!("{}", ValidationError::Keyframed);
+            return false;
+        }
+        let ring: Vec<Point3d> = m
+            .node_ring(&carcass, node)
+            .into_iter()
+            .map(to_screen)
+            .collect();
+        let cursor = Point3d::new(x, y, 0_f64);
+        let near = ring.windows(2).any(|pair| {
+            segment_distance(&cursor, &(pair[0], pair[1])) < constants::RING_GRAB_DISTANCE
+        });
+        let (center, (rad, gm)) = match (
+            m.node_center(&carcass, node),
+            m.muscle().lock().unwrap().get_node(node),
+        ) {
+            (Some(center), Ok(values)) if near => (to_screen(center), values),
+            _ => return false,
+        };
+        self.node_drag = Some(NodeDrag {
+            muscle,
+            node,
+            center,
+            start: Vec3d::from_pts(&center, &cursor).len().max(1_f64),
+            rad,
+            gm,
+            grow_mult,
+        });
+        true
+    }
+
+    // Changes the dragged node: the radius is scaled by the distance of the cursor from
+    // the center, the grow mult is shifted by it. Returns false if there is no drag.
+    pub fn drag_ring(&mut self, x: f64, y: f64) -> bool {
+        let drag = match &self.node_drag {
+            Some(drag) => drag.clone(),
+            None => return false,
+        };
+        let dist = Vec3d::from_pts(&drag.center, &Point3d::new(x, y, 0_f64)).len();
+        let (rad, gm) = if drag.grow_mult {
+            (
+                drag.rad,
+                drag.gm + (dist - drag.start) * constants::GM_DRAG_RATE,
+            )
+        } else {
+            (drag.rad * dist / drag.start, drag.gm)
+        };
+        let mo = MuscleOperation::Mod(MOParams::new(drag.node, rad, gm));
+        let result = self.muscles[drag.muscle]
+            .muscle()
+            .lock()
+            .unwrap()
+            .restruct(mo);
+        match result {
+            Ok(()) => {
+                self.cached_muscles = None;
+                self.drag_change = Some("modify node");
+                self.update_pixbuf();
+            }
+            Err(err) => debug!("Node isn't changed: {}", err),
+        }
+        true
+    }
+
+    // change by the mouse drag is added to the history as one state
+    pub fn end_drag(&mut self) {
+        if let Some(name) = self.drag_change.take() {
+            self.record(name);
+        }
+    }
+
+    // stops the drag and returns the changed node
+    pub fn release_ring(&mut self) -> Option<Pick> {
+        self.node_drag
+            .take()
+            .map(|drag| Pick::Node(drag.muscle, drag.node))
+    }
+
+    fn update_camera(&mut self, operation: Operation, val: f64) {
+        self.transition = None;
         match operation {
-            Operation::Scale => self.matrix.scale_center(val),
-            Operation::Rotate(axis) => self.matrix.rotate_center(val, axis),
-            Operation::Move(axis) => self.matrix.mov(val, axis),
+            Operation::Scale => {
+                let pivot = self.pivot_point();
+                self.camera.zoom_around(&pivot, val);
+            }
+            Operation::ScaleAt(x, y) => self.camera.zoom_at(val, x, y),
+            Operation::Rotate(axis) => {
+                let pivot = self.pivot_point();
+                self.camera.rotate_around(&pivot, val, &axis.vector());
+            }
+            Operation::RotateAround(axis) => {
+                let pivot = self.pivot_point();
+                self.camera.rotate_around(&pivot, val, &axis);
+            }
+            Operation::Move(axis) => {
+                let mut v = axis.vector();
+                v.x *= val;
+                v.y *= val;
+                v.z *= val;
+                self.camera.pan(&v);
+            }
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn pivot(&self) -> Pivot {
+        self.pivot
+    }
+
+    pub fn set_pivot(&mut self, pivot: Pivot) -> Result<(), String> {
+        if let Pivot::Node(muscle, node) = pivot {
+            self.muscles[muscle]
+                .muscle()
+                .lock()
+                .unwrap()
+                .get_node(node)?;
+        }
+        self.pivot = pivot;
+        Ok(())
+    }
+
+    fn scene_bounds(&mut self) -> Option<(Point3d, Point3d)> {
+        self.update_cache();
+        let cached = self
+            .cached_muscles
+            .iter()
+            .flatten()
+            .chain(self.cached_carcass.iter());
+        bounding_box(cached.flat_map(|(points, _)| points.iter().flatten()))
+    }
+
+    // Point of the pivot in the model space: the center of the node is used; if it
+    // doesn't exist anymore, the scene center is used.
+    fn pivot_point(&mut self) -> Point3d {
+        let node = match self.pivot {
+            Pivot::SceneCenter => None,
+            Pivot::Elbow => return self.carcass.lock().unwrap().elbow(),
+            Pivot::Node(muscle, node) => Some((muscle, node)),
+            Pivot::Point(p) => return p,
+        };
+        if let Some((i, node)) = node {
+            let carcass = self.carcass.lock().unwrap();
+            if let Some(p) = self.muscles[i].node_center(&carcass, node) {
+                return p;
+            }
+        }
+        match self.scene_bounds() {
+            Some((min, max)) => Point3d::new(
+                (min.x + max.x) / 2_f64,
+                (min.y + max.y) / 2_f64,
+                (min.z + max.z) / 2_f64,
+            ),
+            None => self.camera.target,
+        }
+    }
+
+    // Arcball rotation by the drag of the mouse from `from` to `to`: the points are
+    // projected on the sphere around the pivot.
+    pub fn arcball(&mut self, from: (f64, f64), to: (f64, f64)) {
+        let mut center = self.pivot_point();
+        self.camera.matrix().apply_to_point(&mut center);
+        let v1 = arcball_vector(from, &center);
+        let v2 = arcball_vector(to, &center);
+        let mut axis = v2.vector_mul(&v1);
+        if axis.len() < f64::EPSILON {
+            return;
+        }
+        axis.normalize();
+        let angle = f64::acos(v1.scalar_mul(&v2).clamp(-1_f64, 1_f64));
+
+        self.update_camera(Operation::RotateAround(axis), angle);
+        self.drag_change = Some("rotate view");
+        self.update_pixbuf();
+    }
+
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.update_camera(Operation::Move(Axis::X), dx);
+        self.update_camera(Operation::Move(Axis::Y), dy);
+        self.drag_change = Some("move view");
+        self.update_pixbuf();
+    }
+
+    // scaling by `SCALE_VAL` per step of the wheel, the point under the cursor stays
+    pub fn zoom_at(&mut self, x: f64, y: f64, steps: f64) {
+        let val = f64::powf(constants::SCALE_VAL, steps);
+        self.update_camera(Operation::ScaleAt(x, y), val);
+        self.record("zoom view");
+        self.update_pixbuf();
+    }
+
+    // camera moves to the view in `VIEW_TRANSITION_FRAMES` frames
+    fn move_camera(&mut self, camera: Camera) {
+        self.transition = Some((self.camera, camera, 0));
+    }
+
+    // Moves the camera by one frame of the transition; returns false if there is no
+    // transition.
+    pub fn camera_frame(&mut self) -> bool {
+        let (from, to, frame) = match self.transition.take() {
+            Some(transition) => transition,
+            None => return false,
+        };
+        let frame = frame + 1;
+        let t = frame as f64 / constants::VIEW_TRANSITION_FRAMES as f64;
+        if frame < constants::VIEW_TRANSITION_FRAMES {
+            self.camera = from.interpolate(&to, t);
+            self.transition = Some((from, to, frame));
+        } else {
+            self.camera = to;
+        }
+        self.update_pixbuf();
+        true
+    }
+
+    pub fn set_view(&mut self, preset: ViewPreset) {
+        let mut camera = self.camera;
+        camera.set_preset(preset);
+        self.move_camera(camera);
+        self.record(&format!("{} view", preset.name().to_lowercase()));
+    }
+
+    pub fn view_names(&self) -> Vec<String> {
+        self.views.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    // current camera is saved with the name, the view with the same name is replaced
+    pub fn save_view(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Name of the view is empty!".to_string());
+        }
+        if ViewPreset::ALL.iter().any(|preset| preset.name() == name) {
+            return Err(format!("{} is the name of the preset!", name));
+        }
+        match self.views.iter_mut().find(|(n, _)| n == name) {
+            Some((_, camera)) => *camera = self.camera,
+            None => self.views.push((name.to_string(), self.camera)),
+        }
+        Ok(())
+    }
+
+    pub fn restore_view(&mut self, name: &str) -> Result<(), String> {
+        match self.views.iter().find(|(n, _)| n == name) {
+            Some(&(_, camera)) => {
+                self.move_camera(camera);
+                self.record(&format!("view {}", name));
+                Ok(())
+            }
+            None => Err(format!("No view with name {}!", name)),
         }
     }
 
-    pub fn process_key(&mut self, key: u16) {
+    // muscles and carcass fill the screen, the orientation stays
+    pub fn zoom_to_fit(&mut self) {
+        if let Some(bounds) = self.scene_bounds() {
+            let mut camera = self.camera;
+            camera.fit(bounds);
+            self.move_camera(camera);
+            self.record("zoom to fit");
+        }
+    }
+
+    pub fn execute(&mut self, command: Command) {
         let time = Instant::now();
-        match key {
-            // operations only with transformation matrix
-            H | L | J | K | F | T | A | S | D | W | Q | E | P | M => {
-                let (operation, val) = match key {
-                    H => (Operation::Rotate(Axis::Y), constants::ROTATE_VAL),
-                    L => (Operation::Rotate(Axis::Y), -constants::ROTATE_VAL),
-                    J => (Operation::Rotate(Axis::X), constants::ROTATE_VAL),
-                    K => (Operation::Rotate(Axis::X), -constants::ROTATE_VAL),
-                    F => (Operation::Rotate(Axis::Z), constants::ROTATE_VAL),
-                    T => (Operation::Rotate(Axis::Z), -constants::ROTATE_VAL),
-
-                    A => (Operation::Move(Axis::X), -constants::MOVE_VAL),
-                    D => (Operation::Move(Axis::X), constants::MOVE_VAL),
-                    S => (Operation::Move(Axis::Y), constants::MOVE_VAL),
-                    W => (Operation::Move(Axis::Y), -constants::MOVE_VAL),
-                    Q => (Operation::Move(Axis::Z), constants::MOVE_VAL),
-                    E => (Operation::Move(Axis::Z), -constants::MOVE_VAL),
-
-                    P => (Operation::Scale, constants::SCALE_VAL),
-                    M => (Operation::Scale, 1_f64 / constants::SCALE_VAL),
+        match command {
+            // operations only with the camera
+            Command::RotateLeft
+            | Command::RotateRight
+            | Command::RotateDown
+            | Command::RotateUp
+            | Command::RotateUnclockwise
+            | Command::RotateClockwise
+            | Command::MoveLeft
+            | Command::MoveRight
+            | Command::MoveDown
+            | Command::MoveUp
+            | Command::MoveFront
+            | Command::MoveBack
+            | Command::ScaleUp
+            | Command::ScaleDown => {
+                let (operation, val) = match command {
+                    Command::RotateLeft => (Operation::Rotate(Axis::Y), constants::ROTATE_VAL),
+                    Command::RotateRight => (Operation::Rotate(Axis::Y), -constants::ROTATE_VAL),
+                    Command::RotateDown => (Operation::Rotate(Axis::X), constants::ROTATE_VAL),
+                    Command::RotateUp => (Operation::Rotate(Axis::X), -constants::ROTATE_VAL),
+                    Command::RotateUnclockwise => {
+                        (Operation::Rotate(Axis::Z), constants::ROTATE_VAL)
+                    }
+                    Command::RotateClockwise => {
+                        (Operation::Rotate(Axis::Z), -constants::ROTATE_VAL)
+                    }
+
+                    Command::MoveLeft => (Operation::Move(Axis::X), -constants::MOVE_VAL),
+                    Command::MoveRight => (Operation::Move(Axis::X), constants::MOVE_VAL),
+                    Command::MoveDown => (Operation::Move(Axis::Y), constants::MOVE_VAL),
+                    Command::MoveUp => (Operation::Move(Axis::Y), -constants::MOVE_VAL),
+                    Command::MoveFront => (Operation::Move(Axis::Z), constants::MOVE_VAL),
+                    Command::MoveBack => (Operation::Move(Axis::Z), -constants::MOVE_VAL),
+
+                    Command::ScaleUp => (Operation::Scale, constants::SCALE_VAL),
+                    Command::ScaleDown => (Operation::Scale, 1_f64 / constants::SCALE_VAL),
                     _ => unreachable!("No way"),
                 };
 
-                self.update_matrix(operation, val);
+                self.update_camera(operation, val);
+                self.record(command.description());
                 debug!(
-                    "{}MATRIX UPDATE TIME: {} ms",
+                    "{}CAMERA UPDATE TIME: {} ms",
                     color::Fg(color::Magenta),
                     time.elapsed().as_millis()
                 );
@@ -177,14 +999,16 @@ impl Controller {
             }
 
             // operations with muscles
-            X | V => {
-                let diff = match key {
-                    X => -constants::ATOM_DIFF,
-                    V => constants::ATOM_DIFF,
+            Command::Shorten | Command::Lengthen => {
+                let diff = match command {
+                    Command::Shorten => -constants::ATOM_DIFF,
+                    Command::Lengthen => constants::ATOM_DIFF,
                     _ => unreachable!("No way"),
                 };
 
-                self.deform(diff);
+                if self.deform(diff) {
+                    self.record(command.description());
+                }
                 debug!(
                     "{}DEFORM TIME: {} ms",
                     color::Fg(color::LightMagenta),
@@ -193,13 +1017,19 @@ impl Controller {
                 self.update_pixbuf();
             }
 
-            // unknown keys
-            val => println!(
-                "{}Unknown command: {}{}",
-                color::Fg(color::Red),
-                val,
-                style::Reset
-            ),
+            Command::ViewFront => self.set_view(ViewPreset::Front),
+            Command::ViewSide => self.set_view(ViewPreset::Side),
+            Command::ViewTop => self.set_view(ViewPreset::Top),
+            Command::ViewAnatomical => self.set_view(ViewPreset::Anatomical),
+            Command::ZoomToFit => self.zoom_to_fit(),
+
+            Command::Simulate => self.toggle_simulation(),
+
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+
+            // handled by the UI
+            Command::Help => (),
         }
         debug!(
             "{}{}TOTAL PROCESSING TIME: {} ms",
@@ -209,3 +1039,20 @@ impl Controller {
         );
     }
 }
+
+// point of the screen on the arcball sphere (the sphere is facing the viewer, the
+// points outside of it are on its edge)
+fn arcball_vector((x, y): (f64, f64), center: &Point3d) -> Vec3d {
+    let mut v = Vec3d::new(
+        (x - center.x) / constants::ARCBALL_RADIUS,
+        (y - center.y) / constants::ARCBALL_RADIUS,
+        0_f64,
+    );
+    let d = v.x * v.x + v.y * v.y;
+    if d < 1_f64 {
+        v.z = f64::sqrt(1_f64 - d);
+    } else {
+        v.normalize();
+    }
+    v
+}
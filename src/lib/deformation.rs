@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+// law, by which the radiuses of the muscle change with its length
+pub trait DeformationModel {
+    // New radiuses of the muscle, which segments change their length from `dx` to
+    // `new_dx`, and the number of made solver iterations. Volume must be kept with the
+    // relative error `tolerance`.
+    fn deform(
+        &self,
+        radiuses: &[f64],
+        grow_mults: &[f64],
+        dx: f64,
+        new_dx: f64,
+        tolerance: f64,
+    ) -> Result<(Vec<f64>, usize), DeformError>;
+}
+
+// deformation model selected in the config of the muscle
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeformationKind {
+    #[default]
+    GrowMults,
+    UniformScaling,
+    PerSegment,
+}
+
+impl DeformationKind {
+    pub fn model(&self) -> &'static dyn DeformationModel {
+        match self {
+            DeformationKind::GrowMults => &GrowMultsModel,
+            DeformationKind::UniformScaling => &UniformScalingModel,
+            DeformationKind::PerSegment => &PerSegmentModel,
+        }
+    }
+}
+
+// All radiuses grow by one value multiplied by their grow multipliers.
+pub struct GrowMultsModel;
+
+impl DeformationModel for GrowMultsModel {
+    fn deform(
+        &self,
+        radiuses: &[f64],
+        grow_mults: &[f64],
+        dx: f64,
+        new_dx: f64,
+        tolerance: f64,
+    ) -> Result<(Vec<f64>, usize), DeformError> {
+        let target = sections_sum(radiuses, grow_mults, 0_f64).0 * dx / new_dx;
+        let (dy, iterations) = solve_growth(radiuses, grow_mults, target, tolerance)?;
+        Ok((grow(radiuses, grow_mults, dy), iterations))
+    }
+}
+
+// All radiuses are scaled by one factor, so the shape of the profile is kept.
+pub struct UniformScalingModel;
+
+impl DeformationModel for UniformScalingModel {
+    fn deform(
+        &self,
+        radiuses: &[f64],
+        _grow_mults: &[f64],
+        dx: f64,
+        new_dx: f64,
+        _tolerance: f64,
+    ) -> Result<(Vec<f64>, usize), DeformError> {
+        let k = f64::sqrt(dx / new_dx);
+        Ok((radiuses.iter().map(|rad| rad * k).collect(), 0))
+    }
+}
+
+// Every segment keeps its own volume growing its radiuses by grow multipliers, so the
+// volume doesn't move along the muscle. Nodes take the mean of the radiuses given by
+// their segments and the profile is scaled to correct the rest of the volume error.
+pub struct PerSegmentModel;
+
+impl DeformationModel for PerSegmentModel {
+    fn deform(
+        &self,
+        radiuses: &[f64],
+        grow_mults: &[f64],
+        dx: f64,
+        new_dx: f64,
+        tolerance: f64,
+    ) -> Result<(Vec<f64>, usize), DeformError> {
+        let mut sums = vec![0_f64; radiuses.len()];
+        let mut counts = vec![0_f64; radiuses.len()];
+        let mut iterations = 0;
+
+        for (i, (rads, mults)) in radiuses.windows(2).zip(grow_mults.windows(2)).enumerate() {
+            let target = sections_sum(rads, mults, 0_f64).0 * dx / new_dx;
+            // segment without grow multipliers is scaled
+            let (new_rads, iters) = match solve_growth(rads, mults, target, tolerance) {
+                Ok((dy, iters)) => (grow(rads, mults, dy), iters),
+                Err(DeformError::NoGrowth) => {
+                    UniformScalingModel.deform(rads, mults, dx, new_dx, tolerance)?
+                }
+                Err(err) => return Err(err),
+            };
+            for (j, rad) in new_rads.into_iter().enumerate() {
+                sums[i + j] += rad;
+                counts[i + j] += 1_f64;
+            }
+            iterations += iters;
+        }
+
+        let new_radiuses: Vec<f64> = sums.iter().zip(counts.iter()).map(|(s, c)| s / c).collect();
+        let k = f64::sqrt(
+            sections_sum(radiuses, grow_mults, 0_f64).0 * dx
+                / (sections_sum(&new_radiuses, grow_mults, 0_f64).0 * new_dx),
+        );
+        Ok((new_radiuses.iter().map(|rad| rad * k).collect(), iterations))
+    }
+}
+
+// Radiuses are interpolated between measured profiles by the length of the muscle and
+// scaled to keep the volume.
+#[derive(Clone, Debug)]
+pub struct KeyframeModel {
+    // (length of the muscle, radiuses) sorted by length
+    frames: Vec<(f64, Vec<f64>)>,
+}
+
+impl KeyframeModel {
+    pub fn new(mut frames: Vec<(f64, Vec<f64>)>, nodes: usize) -> Result<Self, String> {
+        if frames.is_empty() {
+            return Err("No keyframes!".to_string());
+        }
+        if let Some((len, _)) = frames.iter().find(|(_, rads)| rads.len() != nodes) {
+            return Err(format!(
+                "Bad keyframe!\nlen: {:.2};\nnumber of nodes must be {}",
+                len, nodes
+            ));
+        }
+        if let Some((len, _)) = frames.iter().find(|(len, _)| !len.is_finite()) {
+            return Err(format!("Bad keyframe length: {}", len));
+        }
+        frames.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Ok(Self { frames })
+    }
+
+    // profile with the length `len`, the nearest keyframe outside of their range
+    pub fn profile(&self, len: f64) -> Vec<f64> {
+        let last = self.frames.len() - 1;
+        match self.frames.iter().position(|(l, _)| *l > len) {
+            None => self.frames[last].1.clone(),
+            Some(0) => self.frames[0].1.clone(),
+            Some(i) => {
+                let ((l1, r1), (l2, r2)) = (&self.frames[i - 1], &self.frames[i]);
+                let t = (len - l1) / (l2 - l1);
+                r1.iter()
+                    .zip(r2.iter())
+                    .map(|(a, b)| a + (b - a) * t)
+                    .collect()
+            }
+        }
+    }
+}
+
+impl DeformationModel for KeyframeModel {
+    fn deform(
+        &self,
+        radiuses: &[f64],
+        grow_mults: &[f64],
+        dx: f64,
+        new_dx: f64,
+        _tolerance: f64,
+    ) -> Result<(Vec<f64>, usize), DeformError> {
+        let profile = self.profile(new_dx * (radiuses.len() - 1) as f64);
+        let k = f64::sqrt(
+            sections_sum(radiuses, grow_mults, 0_f64).0 * dx
+                / (sections_sum(&profile, grow_mults, 0_f64).0 * new_dx),
+        );
+        Ok((profile.iter().map(|rad| rad * k).collect(), 0))
+    }
+}
+
+// Sum of frustum cross-sections (divided by pi) after the growth of the radiuses by `dy`
+// multiplied by their grow multipliers, and its derivative by `dy`.
+pub fn sections_sum(radiuses: &[f64], grow_mults: &[f64], dy: f64) -> (f64, f64) {
+    let mut res = (0_f64, 0_f64);
+
+    for (rads, mults) in radiuses.windows(2).zip(grow_mults.windows(2)) {
+        let (r1, r2) = (rads[0] + mults[0] * dy, rads[1] + mults[1] * dy);
+        res.0 += (r1 * r1 + r1 * r2 + r2 * r2) / 3_f64;
+        res.1 += (mults[0] * (2_f64 * r1 + r2) + mults[1] * (r1 + 2_f64 * r2)) / 3_f64;
+    }
+
+    res
+}
+
+fn grow(radiuses: &[f64], grow_mults: &[f64], dy: f64) -> Vec<f64> {
+    radiuses
+        .iter()
+        .zip(grow_mults.iter())
+        .map(|(rad, mult)| (rad + mult * dy).max(0_f64))
+        .collect()
+}
+
+// growths with which all the radiuses stay non-negative
+fn growth_range(radiuses: &[f64], grow_mults: &[f64]) -> (f64, f64) {
+    let mut range = (f64::NEG_INFINITY, f64::INFINITY);
+    for (&rad, &mult) in radiuses.iter().zip(grow_mults.iter()) {
+        if mult > 0_f64 {
+            range.0 = f64::max(range.0, -rad / mult);
+        } else if mult < 0_f64 {
+            range.1 = f64::min(range.1, -rad / mult);
+        }
+    }
+    range
+}
+
+// growth with which the sum of sections becomes equal to `target`
+fn solve_growth(
+    radiuses: &[f64],
+    grow_mults: &[f64],
+    target: f64,
+    tolerance: f64,
+) -> Result<(f64, usize), DeformError> {
+    let (sum, der) = sections_sum(radiuses, grow_mults, 0_f64);
+    let tolerance = tolerance * target;
+    let gap = sum - target;
+    if gap.abs() <= tolerance {
+        return Ok((0_f64, 0));
+    }
+    if grow_mults.iter().all(|&mult| mult == 0_f64) {
+        return Err(DeformError::NoGrowth);
+    }
+
+    // sum of sections is convex: go uphill to grow it or downhill to reduce it
+    // (it can't be reduced from its minimum)
+    if gap > 0_f64 && der == 0_f64 {
+        return Err(DeformError::NoSolution);
+    }
+    let dir = match (gap < 0_f64, der >= 0_f64) {
+        (true, true) | (false, false) => 1_f64,
+        _ => -1_f64,
+    };
+    let (low, high) = growth_range(radiuses, grow_mults);
+    let limit = if dir > 0_f64 { high } else { low };
+    let step = if der == 0_f64 {
+        f64::sqrt(gap.abs())
+    } else {
+        (gap / der).abs()
+    };
+
+    let f = |dy| sections_sum(radiuses, grow_mults, dy).0 - target;
+    let (low, high) = expand_bracket(f, 0_f64, step, limit, constants::SOLVER_ITERATIONS)
+        .ok_or(DeformError::NoSolution)?;
+    let (dy, iterations, converged) = solve_bracketed(
+        |dy| {
+            let (sum, der) = sections_sum(radiuses, grow_mults, dy);
+            (sum - target, der)
+        },
+        low,
+        high,
+        tolerance,
+        constants::SOLVER_ITERATIONS,
+    );
+    if !converged {
+        let error = (sections_sum(radiuses, grow_mults, dy).0 - target).abs() / target;
+        return Err(DeformError::NotConverged { error });
+    }
+    Ok((dy, iterations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUSES: [f64; 5] = [2.0, 5.0, 7.0, 4.0, 1.0];
+    const GROW_MULTS: [f64; 5] = [0.1, 1.0, 1.5, 0.8, 0.1];
+    const DX: f64 = 10.0;
+
+    // volume divided by pi
+    fn volume(radiuses: &[f64], dx: f64) -> f64 {
+        sections_sum(radiuses, &GROW_MULTS, 0_f64).0 * dx
+    }
+
+    fn assert_keeps_volume(model: &dyn DeformationModel) {
+        let before = volume(&RADIUSES, DX);
+        for &new_dx in &[0.5 * DX, 0.8 * DX, DX, 1.3 * DX, 2.0 * DX] {
+            let (radiuses, _) = model
+                .deform(
+                    &RADIUSES,
+                    &GROW_MULTS,
+                    DX,
+                    new_dx,
+                    constants::VOLUME_TOLERANCE,
+                )
+                .unwrap();
+            assert_eq!(radiuses.len(), RADIUSES.len());
+            assert!(radiuses.iter().all(|&rad| rad >= 0_f64));
+            let error = (volume(&radiuses, new_dx) - before).abs() / before;
+            assert!(error <= constants::VOLUME_TOLERANCE, "error {:e}", error);
+        }
+    }
+
+    #[test]
+    fn grow_mults_keep_volume() {
+        assert_keeps_volume(&GrowMultsModel);
+    }
+
+    #[test]
+    fn grow_mults_change_radiuses_by_multipliers() {
+        let (radiuses, _) = GrowMultsModel
+            .deform(&RADIUSES, &GROW_MULTS, DX, 0.8 * DX, 1e-12)
+            .unwrap();
+        let dy = (radiuses[1] - RADIUSES[1]) / GROW_MULTS[1];
+        assert!(dy > 0_f64);
+        for i in 0..RADIUSES.len() {
+            assert_relative_eq!(
+                radiuses[i],
+                RADIUSES[i] + GROW_MULTS[i] * dy,
+                epsilon = 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_scaling_keeps_volume_and_shape() {
+        assert_keeps_volume(&UniformScalingModel);
+        let (radiuses, _) = UniformScalingModel
+            .deform(&RADIUSES, &GROW_MULTS, DX, 1.5 * DX, 1e-9)
+            .unwrap();
+        for (rad, old) in radiuses.iter().zip(RADIUSES.iter()) {
+            assert_relative_eq!(rad / old, radiuses[0] / RADIUSES[0], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn per_segment_keeps_volume() {
+        assert_keeps_volume(&PerSegmentModel);
+        // segment without grow multipliers is scaled
+        let mults = [0.0, 0.0, 1.0];
+        let (radiuses, _) = PerSegmentModel
+            .deform(&[3.0, 4.0, 2.0], &mults, DX, 0.7 * DX, 1e-12)
+            .unwrap();
+        let before = sections_sum(&[3.0, 4.0, 2.0], &mults, 0_f64).0 * DX;
+        let after = sections_sum(&radiuses, &mults, 0_f64).0 * 0.7 * DX;
+        assert_relative_eq!(after, before, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn keyframes_keep_volume() {
+        let frames = vec![
+            (60.0, vec![1.0, 4.0, 5.0, 3.0, 1.0]),
+            (30.0, vec![3.0, 7.0, 9.0, 6.0, 2.0]),
+        ];
+        let model = KeyframeModel::new(frames, RADIUSES.len()).unwrap();
+        assert_keeps_volume(&model);
+
+        // profile between the keyframes is interpolated, outside them the nearest is taken
+        assert_eq!(model.profile(20.0), vec![3.0, 7.0, 9.0, 6.0, 2.0]);
+        assert_eq!(model.profile(90.0), vec![1.0, 4.0, 5.0, 3.0, 1.0]);
+        let mid = model.profile(45.0);
+        assert_relative_eq!(mid[2], 7.0);
+        assert_relative_eq!(mid[4], 1.5);
+    }
+
+    #[test]
+    fn bad_keyframes_are_rejected() {
+        assert!(KeyframeModel::new(vec![], 3).is_err());
+        assert!(KeyframeModel::new(vec![(10.0, vec![1.0, 2.0])], 3).is_err());
+        assert!(KeyframeModel::new(vec![(f64::NAN, vec![1.0, 2.0, 1.0])], 3).is_err());
+    }
+
+    #[test]
+    fn solver_reports_failures() {
+        let res = GrowMultsModel.deform(&[1.0, 2.0], &[0.0, 0.0], DX, 0.5 * DX, 1e-9);
+        assert!(matches!(res, Err(DeformError::NoGrowth)));
+        // radiuses would become negative before the volume is kept
+        let res = GrowMultsModel.deform(&[1.0, 10.0], &[1.0, 0.0], DX, 2.0 * DX, 1e-9);
+        assert!(matches!(res, Err(DeformError::NoSolution)));
+    }
+
+    #[test]
+    fn muscle_keeps_length_in_range() {
+        let mut muscle = Muscle::new(RADIUSES.to_vec(), GROW_MULTS.to_vec(), 40.0).unwrap();
+        let (min, max) = muscle.len_range();
+        assert_relative_eq!(min, 40.0 * constants::MIN_PART);
+        assert_relative_eq!(max, 40.0 * constants::MAX_PART);
+
+        for &diff in &[max - 40.0 + 1.0, min - 40.0 - 1.0] {
+            match muscle.deform(diff) {
+                Err(DeformError::OutOfRange { len, .. }) => {
+                    assert_relative_eq!(len, 40.0 + diff)
+                }
+                res => panic!("length {} is allowed: {:?}", 40.0 + diff, res.is_ok()),
+            }
+            assert_relative_eq!(muscle.len(), 40.0);
+            assert_eq!(muscle.radiuses(), &RADIUSES[..]);
+        }
+
+        let volume = muscle.volume();
+        let report = muscle.deform(max - 40.0).unwrap();
+        assert_relative_eq!(muscle.len(), max);
+        assert!(report.volume_error <= constants::VOLUME_TOLERANCE);
+        assert_relative_eq!(muscle.volume(), volume, max_relative = 1e-9);
+        assert!(muscle.deform(min - max).is_ok());
+        assert_relative_eq!(muscle.len(), min);
+    }
+}
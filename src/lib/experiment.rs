@@ -0,0 +1,281 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::prelude::*;
+
+// radiuses of the real muscle and of the model posed at the measured angles
+pub struct Comparison {
+    pub angles: Vec<f64>,
+    pub nodes: Vec<usize>,
+    // [angle][node]
+    pub real: Vec<Vec<f64>>,
+    pub model: Vec<Vec<f64>>,
+}
+
+impl Comparison {
+    pub fn new(config: &Config, index: usize, measurements: &Measurements) -> Result<Self, String> {
+        let mconf = config
+            .muscles
+            .get(index)
+            .ok_or(format!("No muscle with index {}!", index))?;
+        if let Some(&node) = measurements
+            .nodes
+            .iter()
+            .find(|&&node| node >= mconf.radiuses.len())
+        {
+            return Err(format!(
+                "Bad node!\nnode: {};\nnumber of nodes: {}.",
+                node,
+                mconf.radiuses.len()
+            ));
+        }
+        let (muscles, carcass) = build_scene(config.clone())?;
+        let model = pose_radiuses(
+            mconf,
+            &mconf.radiuses,
+            &mconf.grow_mults,
+            &carcass,
+            muscles[index].attachment(),
+            measurements,
+        )
+        .map_err(|err| format!("Can't pose the muscle!\n{}", err))?;
+
+        Ok(Self {
+            angles: measurements.angles.clone(),
+            nodes: measurements.nodes.clone(),
+            real: measurements.radiuses.clone(),
+            model,
+        })
+    }
+
+    // model radius minus real one for every node at the angle with index `a`
+    pub fn errors(&self, a: usize) -> Vec<f64> {
+        self.model[a]
+            .iter()
+            .zip(self.real[a].iter())
+            .map(|(model, real)| model - real)
+            .collect()
+    }
+
+    pub fn rmse(&self, a: usize) -> f64 {
+        rmse(self.model[a].iter().zip(self.real[a].iter()))
+    }
+
+    pub fn max_error(&self, a: usize) -> f64 {
+        self.errors(a)
+            .iter()
+            .fold(0_f64, |max, err| max.max(err.abs()))
+    }
+
+    pub fn total_rmse(&self) -> f64 {
+        rmse(self.model.iter().flatten().zip(self.real.iter().flatten()))
+    }
+
+    pub fn summary(&self) -> String {
+        let mut text = format!("{:>8}{:>10}{:>10}\n", "angle", "rmse", "max");
+        for (a, angle) in self.angles.iter().enumerate() {
+            text += &format!(
+                "{:>8.1}{:>10.3}{:>10.3}\n",
+                angle,
+                self.rmse(a),
+                self.max_error(a)
+            );
+        }
+        text + &format!("   total{:>10.3}\n", self.total_rmse())
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(writer, "angle,node,real,model,error")?;
+        for (a, angle) in self.angles.iter().enumerate() {
+            for (n, (node, err)) in self.nodes.iter().zip(self.errors(a)).enumerate() {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{}",
+                    angle, node, self.real[a][n], self.model[a][n], err
+                )?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    // table of model radiuses in the style of the research chapter with errors at the end
+    pub fn latex_table(&self, caption: &str, label: &str) -> String {
+        let columns = "|c".repeat(self.angles.len() + 1) + "|";
+        let angles: String = self
+            .angles
+            .iter()
+            .map(|angle| format!(" & {}$^\\circ$", angle))
+            .collect();
+
+        let mut text = String::from("\\begin{table}[!h]\n    \\begin{center}\n");
+        text += &format!("        \\begin{{tabular}}{{{}}}\n", columns);
+        text += "            \\hline\n";
+        text += &format!(
+            "            {}{} \\\\\n",
+            constants::COMPARE_NODE_HEADING,
+            angles
+        );
+        text += "            \\hline\n            \\hline\n";
+        for (n, node) in self.nodes.iter().enumerate() {
+            let row: String = (0..self.angles.len())
+                .map(|a| format!(" & {:.1}", self.model[a][n]))
+                .collect();
+            text += &format!("            {}{} \\\\\n            \\hline\n", node, row);
+        }
+        let rmse: String = (0..self.angles.len())
+            .map(|a| format!(" & {:.2}", self.rmse(a)))
+            .collect();
+        let max: String = (0..self.angles.len())
+            .map(|a| format!(" & {:.2}", self.max_error(a)))
+            .collect();
+        text += "            \\hline\n";
+        text += &format!("            RMSE{} \\\\\n            \\hline\n", rmse);
+        text += &format!(
+            "            {}{} \\\\\n            \\hline\n",
+            constants::COMPARE_MAX_ERROR_HEADING,
+            max
+        );
+        text += "        \\end{tabular}\n    \\end{center}\n";
+        text += &format!("    \\caption{{\\label{{{}}} {}}}\n", label, caption);
+        text + "\\end{table}\n"
+    }
+
+    // `c<angle>.dat` file for every angle with columns `node real model error`
+    pub fn write_pgfplots<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        for (a, angle) in self.angles.iter().enumerate() {
+            let path = dir.as_ref().join(format!("c{}.dat", angle));
+            let mut writer = BufWriter::new(File::create(path)?);
+
+            writeln!(writer, "node real model error")?;
+            for (n, (node, err)) in self.nodes.iter().zip(self.errors(a)).enumerate() {
+                writeln!(
+                    writer,
+                    "{} {} {} {}",
+                    node, self.real[a][n], self.model[a][n], err
+                )?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// `compare CONFIG MEASUREMENTS OUTPUT_DIR [--muscle NAME] [--caption TEXT] [--label LABEL]`
+pub fn compare_command(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: compare CONFIG MEASUREMENTS OUTPUT_DIR [--muscle NAME] [--caption TEXT] \
+                 [--label LABEL]";
+    let mut paths = Vec::new();
+    let mut muscle = None;
+    let mut caption = constants::COMPARE_CAPTION.to_string();
+    let mut label = constants::COMPARE_LABEL.to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--muscle" => muscle = Some(args.next().ok_or(usage)?.clone()),
+            "--caption" => caption = args.next().ok_or(usage)?.clone(),
+            "--label" => label = args.next().ok_or(usage)?.clone(),
+            _ if arg.starts_with("--") => return Err(usage.to_string()),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.len() != 3 {
+        return Err(usage.to_string());
+    }
+
+    let config = read_config(&paths[0])?;
+    let measurements = Measurements::read(&paths[1])?;
+    let index = muscle_index(&config, muscle.as_deref())?;
+    let comparison = Comparison::new(&config, index, &measurements)?;
+    print!("{}", comparison.summary());
+
+    let dir = Path::new(&paths[2]);
+    let write_err = |err: io::Error| format!("Can't write report!\n{}", err);
+    fs::create_dir_all(dir).map_err(write_err)?;
+    comparison
+        .write_csv(dir.join("comparison.csv"))
+        .map_err(write_err)?;
+    fs::write(
+        dir.join("comparison.tex"),
+        comparison.latex_table(&caption, &label),
+    )
+    .map_err(write_err)?;
+    comparison.write_pgfplots(dir).map_err(write_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison() -> Comparison {
+        Comparison {
+            angles: vec![0.0, 90.0],
+            nodes: vec![1, 3],
+            real: vec![vec![10.0, 20.0], vec![12.0, 25.0]],
+            model: vec![vec![11.0, 18.0], vec![12.0, 26.0]],
+        }
+    }
+
+    #[test]
+    fn errors_are_found_by_angle() {
+        let c = comparison();
+        assert_eq!(c.errors(0), vec![1.0, -2.0]);
+        assert_relative_eq!(c.rmse(0), f64::sqrt(2.5));
+        assert_relative_eq!(c.max_error(0), 2.0);
+        assert_relative_eq!(c.total_rmse(), f64::sqrt(1.5));
+    }
+
+    #[test]
+    fn csv_has_row_for_every_node_and_angle() {
+        let path = std::env::temp_dir().join("experiment_test_comparison.csv");
+        comparison().write_csv(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "angle,node,real,model,error\n\
+             0,1,10,11,1\n\
+             0,3,20,18,-2\n\
+             90,1,12,12,0\n\
+             90,3,25,26,1\n"
+        );
+    }
+
+    #[test]
+    fn latex_table_has_model_radiuses_and_errors() {
+        let table = comparison().latex_table("Caption", "tab:test");
+        let expected = r"\begin{table}[!h]
+    \begin{center}
+        \begin{tabular}{|c|c|c|}
+            \hline
+            Номер узла & 0$^\circ$ & 90$^\circ$ \\
+            \hline
+            \hline
+            1 & 11.0 & 12.0 \\
+            \hline
+            3 & 18.0 & 26.0 \\
+            \hline
+            \hline
+            RMSE & 1.58 & 0.71 \\
+            \hline
+            Макс. ошибка & 2.00 & 1.00 \\
+            \hline
+        \end{tabular}
+    \end{center}
+    \caption{\label{tab:test} Caption}
+\end{table}
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn unknown_option_is_rejected() {
+        let args: Vec<String> = ["config.yaml", "real.csv", "report", "--lable", "tab:x"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let err = compare_command(&args).unwrap_err();
+        assert!(err.starts_with("Usage: compare"));
+    }
+}
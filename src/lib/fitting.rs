@@ -0,0 +1,540 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+use super::prelude::*;
+
+// measured radiuses of the muscle nodes in several poses
+pub struct Measurements {
+    // flexion angles in degrees (0 is the extended arm)
+    pub angles: Vec<f64>,
+    // indexes of the measured nodes
+    pub nodes: Vec<usize>,
+    // radiuses[angle][node]
+    pub radiuses: Vec<Vec<f64>>,
+}
+
+impl Measurements {
+    // Reads the table as `docs/tex_src/assets/csv/real.csv`: the header with the node
+    // column and `c<angle>` columns, then rows with the node index and its radiuses.
+    // Columns are separated by `,` or `|`. Tables without values are rejected, so RMSE of
+    // every pose exists.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Can't read file!\n{}", err))?;
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let split = |line: &str| -> Vec<String> {
+            line.split([',', '|'])
+                .map(|col| col.trim().to_string())
+                .collect()
+        };
+
+        let header = lines.next().ok_or("Empty measurements!")?;
+        let angles = split(header)
+            .iter()
+            .skip(1)
+            .map(|col| {
+                col.trim_start_matches('c')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|angle| angle.is_finite())
+                    .ok_or(format!("Bad angle column: {}", col))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        let mut nodes = Vec::new();
+        let mut radiuses = vec![Vec::new(); angles.len()];
+        for line in lines {
+            let cols = split(line);
+            if cols.len() != angles.len() + 1 {
+                return Err(format!("Bad number of columns: {}", line));
+            }
+            nodes.push(
+                cols[0]
+                    .parse::<usize>()
+                    .map_err(|_| format!("Bad node: {}", cols[0]))?,
+            );
+            for (rads, col) in radiuses.iter_mut().zip(cols.iter().skip(1)) {
+                rads.push(
+                    col.parse::<f64>()
+                        .ok()
+                        .filter(|rad| rad.is_finite())
+                        .ok_or(format!("Bad radius: {}", col))?,
+                );
+            }
+        }
+        if angles.is_empty() || nodes.is_empty() {
+            return Err("Empty measurements!".to_string());
+        }
+
+        Ok(Self {
+            angles,
+            nodes,
+            radiuses,
+        })
+    }
+}
+
+pub struct FitResult {
+    pub radiuses: Vec<f64>,
+    pub grow_mults: Vec<f64>,
+    // model radiuses of the measured nodes in every pose before and after fitting
+    pub initial: Vec<Vec<f64>>,
+    pub fitted: Vec<Vec<f64>>,
+    pub iterations: usize,
+}
+
+// Radiuses of the measured nodes of the muscle deformed from its config length to every
+// measured pose.
+pub fn pose_radiuses(
+    mconf: &MuscleConfig,
+    radiuses: &[f64],
+    grow_mults: &[f64],
+    carcass: &Carcass,
+    attachment: &Attachment,
+    measurements: &Measurements,
+) -> Result<Vec<Vec<f64>>, String> {
+    // broken keyframes are reported, when the scene is built
+    let keyframes = keyframe_model(mconf, carcass, attachment).unwrap_or(None);
+    measurements
+        .angles
+        .iter()
+        .map(|angle| {
+            let mut muscle = Muscle::new(radiuses.to_vec(), grow_mults.to_vec(), mconf.len)?;
+            muscle.set_model(mconf.deformation);
+            muscle.set_keyframes(keyframes.clone());
+            if let Some(tolerance) = mconf.tolerance {
+                muscle.set_tolerance(tolerance);
+            }
+            let len = carcass.muscle_len_at(attachment, std::f64::consts::PI - angle.to_radians());
+            muscle
+                .deform(len - mconf.len)
+                .map_err(|err| err.to_string())?;
+            Ok(measurements
+                .nodes
+                .iter()
+                .map(|&node| muscle.radiuses()[node])
+                .collect())
+        })
+        .collect()
+}
+
+// Finds grow multipliers (and base radiuses if `fit_radiuses`) of the measured nodes of
+// the muscle with index `index`, which minimize RMSE of the model radiuses.
+pub fn fit(
+    config: &Config,
+    index: usize,
+    measurements: &Measurements,
+    fit_radiuses: bool,
+) -> Result<FitResult, String> {
+    let mconf = config
+        .muscles
+        .get(index)
+        .ok_or(format!("No muscle with index {}!", index))?;
+    if let Some(&node) = measurements
+        .nodes
+        .iter()
+        .find(|&&node| node >= mconf.radiuses.len())
+    {
+        return Err(format!(
+            "Bad node!\nnode: {};\nnumber of nodes: {}.",
+            node,
+            mconf.radiuses.len()
+        ));
+    }
+    let (muscles, carcass) = build_scene(config.clone())?;
+    let attachment = *muscles[index].attachment();
+
+    // parameters are grow multipliers of the measured nodes, then their radiuses
+    let mut params: Vec<f64> = measurements
+        .nodes
+        .iter()
+        .map(|&node| mconf.grow_mults[node])
+        .collect();
+    if fit_radiuses {
+        params.extend(measurements.nodes.iter().map(|&node| mconf.radiuses[node]));
+    }
+    let profile = |params: &[f64]| {
+        let (mut radiuses, mut grow_mults) = (mconf.radiuses.clone(), mconf.grow_mults.clone());
+        let n = measurements.nodes.len();
+        for (i, &node) in measurements.nodes.iter().enumerate() {
+            grow_mults[node] = params[i];
+            if fit_radiuses {
+                radiuses[node] = params[n + i];
+            }
+        }
+        (radiuses, grow_mults)
+    };
+    let model = |params: &[f64]| {
+        let (radiuses, grow_mults) = profile(params);
+        pose_radiuses(
+            mconf,
+            &radiuses,
+            &grow_mults,
+            &carcass,
+            &attachment,
+            measurements,
+        )
+    };
+    let residuals = |params: &[f64]| {
+        model(params).ok().map(|poses| {
+            poses
+                .iter()
+                .flatten()
+                .zip(measurements.radiuses.iter().flatten())
+                .map(|(model, real)| model - real)
+                .collect()
+        })
+    };
+
+    let initial =
+        model(&params).map_err(|err| format!("Can't deform the initial muscle!\n{}", err))?;
+    let (params, iterations) = levenberg_marquardt(residuals, params)?;
+    let (radiuses, grow_mults) = profile(&params);
+    let fitted = model(&params)?;
+
+    Ok(FitResult {
+        radiuses,
+        grow_mults,
+        initial,
+        fitted,
+        iterations,
+    })
+}
+
+// pairs must not be empty
+pub fn rmse<'a, I: Iterator<Item = (&'a f64, &'a f64)>>(pairs: I) -> f64 {
+    let (sum, n) = pairs.fold((0_f64, 0), |(sum, n), (a, b)| {
+        (sum + (a - b) * (a - b), n + 1)
+    });
+    f64::sqrt(sum / n as f64)
+}
+
+pub fn residuals_report(measurements: &Measurements, result: &FitResult) -> String {
+    let mut text = format!(
+        "{:>8}{:>6}{:>10}{:>10}{:>10}{:>10}\n",
+        "angle", "node", "real", "initial", "fitted", "residual"
+    );
+    for (a, angle) in measurements.angles.iter().enumerate() {
+        for (n, node) in measurements.nodes.iter().enumerate() {
+            let real = measurements.radiuses[a][n];
+            text += &format!(
+                "{:>8.1}{:>6}{:>10.2}{:>10.2}{:>10.2}{:>10.2}\n",
+                angle,
+                node,
+                real,
+                result.initial[a][n],
+                result.fitted[a][n],
+                result.fitted[a][n] - real
+            );
+        }
+    }
+
+    text += "\nRMSE (initial -> fitted):\n";
+    for (a, angle) in measurements.angles.iter().enumerate() {
+        text += &format!(
+            "{:>8.1}: {:.3} -> {:.3}\n",
+            angle,
+            rmse(
+                result.initial[a]
+                    .iter()
+                    .zip(measurements.radiuses[a].iter())
+            ),
+            rmse(result.fitted[a].iter().zip(measurements.radiuses[a].iter()))
+        );
+    }
+    let real = measurements.radiuses.iter().flatten();
+    text + &format!(
+        "   total: {:.3} -> {:.3} ({} iterations)\n",
+        rmse(result.initial.iter().flatten().zip(real.clone())),
+        rmse(result.fitted.iter().flatten().zip(real)),
+        result.iterations
+    )
+}
+
+// index of the muscle with the name `name` in the config, the first one by default
+pub fn muscle_index(config: &Config, name: Option<&str>) -> Result<usize, String> {
+    match name {
+        Some(name) => config
+            .muscles
+            .iter()
+            .position(|m| m.name == name)
+            .ok_or(format!("No muscle with name {}!", name)),
+        None => Ok(0),
+    }
+}
+
+// `fit CONFIG MEASUREMENTS OUTPUT [--muscle NAME] [--radiuses] [--report PATH]`
+pub fn fit_command(args: &[String]) -> Result<(), String> {
+    let usage =
+        "Usage: fit CONFIG MEASUREMENTS OUTPUT [--muscle NAME] [--radiuses] [--report PATH]";
+    let mut paths = Vec::new();
+    let (mut muscle, mut fit_radiuses, mut report_path) = (None, false, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--muscle" => muscle = Some(args.next().ok_or(usage)?.clone()),
+            "--radiuses" => fit_radiuses = true,
+            "--report" => report_path = Some(args.next().ok_or(usage)?.clone()),
+            _ if arg.starts_with("--") => return Err(usage.to_string()),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.len() != 3 {
+        return Err(usage.to_string());
+    }
+
+    let mut config = read_config(&paths[0])?;
+    let measurements = Measurements::read(&paths[1])?;
+    let index = muscle_index(&config, muscle.as_deref())?;
+
+    let result = fit(&config, index, &measurements, fit_radiuses)?;
+    let report = residuals_report(&measurements, &result);
+    print!("{}", report);
+    if let Some(path) = report_path {
+        fs::write(path, &report).map_err(|err| format!("Can't write report!\n{}", err))?;
+    }
+
+    config.muscles[index].radiuses = result.radiuses;
+    config.muscles[index].grow_mults = result.grow_mults;
+    let yaml = serde_yaml::to_string(&config).map_err(|err| err.to_string())?;
+    fs::write(&paths[2], yaml).map_err(|err| format!("Can't write config!\n{}", err))
+}
+
+// Minimizes the sum of squared residuals, the Jacobian is found by finite differences.
+// Steps on which residuals can't be computed are rejected.
+fn levenberg_marquardt<F: Fn(&[f64]) -> Option<Vec<f64>>>(
+    residuals: F,
+    mut params: Vec<f64>,
+) -> Result<(Vec<f64>, usize), String> {
+    let cost = |r: &[f64]| r.iter().map(|v| v * v).sum::<f64>();
+    let mut res = residuals(&params).ok_or("Can't deform the initial muscle!")?;
+    let mut lambda = constants::FIT_LAMBDA;
+
+    for iteration in 0..constants::FIT_ITERATIONS {
+        let jacobian: Vec<Vec<f64>> = (0..params.len())
+            .map(|j| {
+                let h = constants::FIT_STEP * f64::max(1_f64, params[j].abs());
+                [h, -h]
+                    .iter()
+                    .find_map(|&h| {
+                        let mut moved = params.clone();
+                        moved[j] += h;
+                        residuals(&moved)
+                            .map(|r| r.iter().zip(res.iter()).map(|(a, b)| (a - b) / h).collect())
+                    })
+                    .unwrap_or_else(|| vec![0_f64; res.len()])
+            })
+            .collect();
+
+        let n = params.len();
+        let mut jtj = vec![vec![0_f64; n]; n];
+        let mut jtr = vec![0_f64; n];
+        for i in 0..n {
+            for k in 0..n {
+                jtj[i][k] = jacobian[i]
+                    .iter()
+                    .zip(jacobian[k].iter())
+                    .map(|(a, b)| a * b)
+                    .sum();
+            }
+            jtr[i] = jacobian[i].iter().zip(res.iter()).map(|(a, b)| a * b).sum();
+        }
+
+        let old_cost = cost(&res);
+        let mut accepted = None;
+        for _ in 0..constants::FIT_DAMPING_STEPS {
+            let mut a = jtj.clone();
+            for i in 0..n {
+                a[i][i] += lambda * (jtj[i][i] + constants::FIT_STEP);
+            }
+            let step = solve_linear(a, jtr.iter().map(|v| -v).collect())?;
+            let trial = step.and_then(|step| {
+                let moved: Vec<f64> = params.iter().zip(step.iter()).map(|(p, s)| p + s).collect();
+                residuals(&moved).map(|r| (moved, r))
+            });
+            match trial {
+                Some((moved, r)) if cost(&r) < old_cost => {
+                    accepted = Some((moved, r));
+                    lambda /= 10_f64;
+                    break;
+                }
+                _ => lambda *= 10_f64,
+            }
+        }
+
+        match accepted {
+            Some((moved, r)) => {
+                let new_cost = cost(&r);
+                params = moved;
+                res = r;
+                if old_cost - new_cost <= constants::FIT_TOLERANCE * old_cost {
+                    return Ok((params, iteration + 1));
+                }
+            }
+            None => return Ok((params, iteration)),
+        }
+    }
+    Ok((params, constants::FIT_ITERATIONS))
+}
+
+// Gaussian elimination with partial pivoting, None for the singular matrix. Not finite
+// values of the system are an error.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Result<Option<Vec<f64>>, String> {
+    if a.iter().flatten().chain(b.iter()).any(|v| !v.is_finite()) {
+        return Err("Not finite values in the fitting!".to_string());
+    }
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| {
+                a[i][col]
+                    .abs()
+                    .partial_cmp(&a[j][col].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap_or(col);
+        if relative_eq!(a[pivot][col], 0_f64) {
+            return Ok(None);
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in (col + 1)..n {
+            let k = a[row][col] / pivot_row[col];
+            for (elem, pivot_elem) in a[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *elem -= k * pivot_elem;
+            }
+            b[row] -= k * b[col];
+        }
+    }
+
+    let mut x = vec![0_f64; n];
+    for row in (0..n).rev() {
+        let sum: f64 = ((row + 1)..n).map(|i| a[row][i] * x[i]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    // overflow of the elimination is taken as the singular matrix
+    if x.iter().any(|v| !v.is_finite()) {
+        return Ok(None);
+    }
+    Ok(Some(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+muscles:
+  - name: biceps
+    radiuses:   [5.0, 5.0, 10.0, 15.0, 18.0, 18.0, 15.0, 10.0, 5.0, 5.0]
+    grow_mults: [0.0, 1.0,  1.0,  2.0,  2.0,  2.0,  2.0,  1.0, 1.0, 0.0]
+    len: 250.0
+carcass_config:
+  data: [[30.0, 230.0], [30.0, 120.0]]
+  thickness: 5.0
+";
+
+    fn write_temp(name: &str, text: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("fitting_test_{}.csv", name));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn measurements_are_parsed() {
+        let path = write_temp("good", "size|c0|c30.5\n1, 7.6 |16.2\n\n3,20.7,22.5\n");
+        let m = Measurements::read(&path).unwrap();
+        assert_eq!(m.angles, vec![0.0, 30.5]);
+        assert_eq!(m.nodes, vec![1, 3]);
+        assert_eq!(m.radiuses, vec![vec![7.6, 20.7], vec![16.2, 22.5]]);
+    }
+
+    #[test]
+    fn bad_measurements_are_rejected() {
+        for (name, text) in &[
+            ("empty", "\n"),
+            ("no_rows", "size|c0|c30\n"),
+            ("no_angles", "size\n1\n"),
+            ("angle", "size|c0|cx\n1,2,3\n"),
+            ("columns", "size|c0|c30\n1,2\n"),
+            ("node", "size|c0\n-1,2\n"),
+            ("radius", "size|c0\n1,NaN\n"),
+        ] {
+            let path = write_temp(name, text);
+            assert!(Measurements::read(&path).is_err(), "{} is accepted", name);
+        }
+        assert!(Measurements::read("/nonexistent/real.csv").is_err());
+    }
+
+    #[test]
+    fn unknown_option_is_rejected() {
+        let args: Vec<String> = ["config.yaml", "real.csv", "out.yaml", "--radius"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let err = fit_command(&args).unwrap_err();
+        assert!(err.starts_with("Usage: fit"));
+    }
+
+    #[test]
+    fn linear_system_is_solved() {
+        let a = vec![
+            vec![2.0, 1.0, -1.0],
+            vec![-3.0, -1.0, 2.0],
+            vec![-2.0, 1.0, 2.0],
+        ];
+        let x = solve_linear(a, vec![8.0, -11.0, -3.0]).unwrap().unwrap();
+        for (x, expected) in x.iter().zip([2.0, 3.0, -1.0].iter()) {
+            assert_relative_eq!(x, expected, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn singular_system_has_no_solution() {
+        let a = vec![
+            vec![1.0, 2.0, 3.0],
+            vec![2.0, 4.0, 6.0],
+            vec![1.0, 0.0, 1.0],
+        ];
+        assert!(solve_linear(a, vec![1.0, 2.0, 3.0]).unwrap().is_none());
+        assert!(solve_linear(vec![vec![0.0]], vec![1.0]).unwrap().is_none());
+        assert!(solve_linear(vec![vec![f64::NAN]], vec![1.0]).is_err());
+    }
+
+    #[test]
+    fn fit_finds_grow_mults_of_synthetic_measurements() {
+        let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let mconf = &config.muscles[0];
+        let mut true_mults = mconf.grow_mults.clone();
+        let nodes = vec![2, 3, 4, 5, 6];
+        for (&node, mult) in nodes.iter().zip([1.5, 2.5, 3.0, 1.0, 2.2].iter()) {
+            true_mults[node] = *mult;
+        }
+
+        let (muscles, carcass) = build_scene(config.clone()).unwrap();
+        let mut measurements = Measurements {
+            angles: vec![20.0, 60.0, 90.0, 120.0],
+            nodes: nodes.clone(),
+            radiuses: Vec::new(),
+        };
+        measurements.radiuses = pose_radiuses(
+            mconf,
+            &mconf.radiuses,
+            &true_mults,
+            &carcass,
+            muscles[0].attachment(),
+            &measurements,
+        )
+        .unwrap();
+
+        let result = fit(&config, 0, &measurements, false).unwrap();
+        let real = measurements.radiuses.iter().flatten();
+        assert!(rmse(result.initial.iter().flatten().zip(real.clone())) > 0.1);
+        assert!(rmse(result.fitted.iter().flatten().zip(real)) < 1e-4);
+        for &node in &nodes {
+            assert_relative_eq!(result.grow_mults[node], true_mults[node], epsilon = 1e-3);
+        }
+        assert_eq!(result.radiuses, mconf.radiuses);
+    }
+}
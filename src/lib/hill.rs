@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+// Parameters of the Hill-type muscle-tendon unit. Lengths are in model units,
+// force is in newtons, velocity is in optimal fiber lengths per second.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct HillParams {
+    pub max_force: f64,
+    pub optimal_fiber_len: f64,
+    pub tendon_slack_len: f64,
+    #[serde(default = "default_max_velocity")]
+    pub max_velocity: f64,
+    #[serde(default = "default_activation_time")]
+    pub activation_time: f64,
+    #[serde(default = "default_deactivation_time")]
+    pub deactivation_time: f64,
+}
+
+fn default_max_velocity() -> f64 {
+    constants::HILL_MAX_VELOCITY
+}
+
+fn default_activation_time() -> f64 {
+    constants::HILL_ACTIVATION_TIME
+}
+
+fn default_deactivation_time() -> f64 {
+    constants::HILL_DEACTIVATION_TIME
+}
+
+impl HillParams {
+    // Parameters for the muscle without measured ones: fibers take `HILL_FIBER_PART` of
+    // the rest length, maximal force is proportional to the physiological cross-section
+    // (volume divided by the fiber length).
+    pub fn from_muscle(muscle: &Muscle) -> Self {
+        let len = muscle.len();
+        let optimal_fiber_len = len * constants::HILL_FIBER_PART;
+        Self {
+            max_force: muscle.volume() / optimal_fiber_len * constants::SPECIFIC_TENSION,
+            optimal_fiber_len,
+            tendon_slack_len: len - optimal_fiber_len,
+            max_velocity: constants::HILL_MAX_VELOCITY,
+            activation_time: constants::HILL_ACTIVATION_TIME,
+            deactivation_time: constants::HILL_DEACTIVATION_TIME,
+        }
+    }
+
+    // time derivative of the activation for the excitation `u`
+    pub fn activation_derivative(&self, u: f64, activation: f64) -> f64 {
+        let time = if u > activation {
+            self.activation_time
+        } else {
+            self.deactivation_time
+        };
+        (u - activation) / time
+    }
+
+    pub fn tendon_force(&self, tendon_len: f64) -> f64 {
+        self.max_force
+            * tendon_force_mult((tendon_len - self.tendon_slack_len) / self.tendon_slack_len)
+    }
+
+    // force of the fibers with given activation, length and velocity (units per second)
+    pub fn fiber_force(&self, activation: f64, fiber_len: f64, velocity: f64) -> f64 {
+        let norm_len = fiber_len / self.optimal_fiber_len;
+        let norm_vel = velocity / (self.max_velocity * self.optimal_fiber_len);
+        self.max_force
+            * (activation * active_force_mult(norm_len) * velocity_force_mult(norm_vel)
+                + passive_force_mult(norm_len))
+    }
+
+    // Velocity of the fibers (units per second) which makes their force equal to the
+    // force of the tendon (massless fibers, no pennation).
+    pub fn fiber_velocity(&self, activation: f64, fiber_len: f64, tendon_force: f64) -> f64 {
+        let norm_len = fiber_len / self.optimal_fiber_len;
+        let fv = (tendon_force / self.max_force - passive_force_mult(norm_len))
+            / (activation.max(constants::HILL_MIN_ACTIVATION)
+                * active_force_mult(norm_len).max(constants::HILL_MIN_ACTIVE_FORCE));
+        inverse_velocity_force_mult(fv) * self.max_velocity * self.optimal_fiber_len
+    }
+
+    // fiber length with which the unit of length `mtu_len` is in static equilibrium
+    pub fn equilibrium_fiber_len(&self, activation: f64, mtu_len: f64) -> f64 {
+        let (mut low, mut high) = (
+            self.optimal_fiber_len * constants::HILL_MIN_FIBER_PART,
+            mtu_len - self.tendon_slack_len,
+        );
+        if high <= low {
+            return low;
+        }
+        // fiber force grows and tendon force falls with the fiber length
+        for _ in 0..constants::HILL_ITERATIONS {
+            let mid = (low + high) / 2_f64;
+            if self.fiber_force(activation, mid, 0_f64) < self.tendon_force(mtu_len - mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2_f64
+    }
+}
+
+// force-length relationship of the contractile element
+pub fn active_force_mult(norm_len: f64) -> f64 {
+    f64::exp(-f64::powi((norm_len - 1_f64) / constants::HILL_FL_WIDTH, 2))
+}
+
+// force-length relationship of the parallel elastic element
+pub fn passive_force_mult(norm_len: f64) -> f64 {
+    if norm_len <= 1_f64 {
+        return 0_f64;
+    }
+    (f64::exp(constants::HILL_PE_SHAPE * (norm_len - 1_f64) / constants::HILL_PE_STRAIN) - 1_f64)
+        / (f64::exp(constants::HILL_PE_SHAPE) - 1_f64)
+}
+
+// Force-velocity relationship: Hill hyperbola for shortening (negative velocity) and
+// saturating hyperbola up to `HILL_MAX_LENGTHENING_FORCE` for lengthening.
+pub fn velocity_force_mult(norm_vel: f64) -> f64 {
+    if norm_vel <= -1_f64 {
+        0_f64
+    } else if norm_vel <= 0_f64 {
+        (1_f64 + norm_vel) / (1_f64 - norm_vel / constants::HILL_FV_SHAPE)
+    } else {
+        1_f64
+            + (constants::HILL_MAX_LENGTHENING_FORCE - 1_f64) * norm_vel
+                / (norm_vel + constants::HILL_FV_LENGTHENING_SHAPE)
+    }
+}
+
+fn inverse_velocity_force_mult(fv: f64) -> f64 {
+    if fv <= 0_f64 {
+        -1_f64
+    } else if fv <= 1_f64 {
+        (fv - 1_f64) / (1_f64 + fv / constants::HILL_FV_SHAPE)
+    } else {
+        let fv = fv.min(constants::HILL_MAX_LENGTHENING_FORCE - constants::HILL_FV_EPS);
+        constants::HILL_FV_LENGTHENING_SHAPE * (fv - 1_f64)
+            / (constants::HILL_MAX_LENGTHENING_FORCE - fv)
+    }
+}
+
+// strain of the tendon with given force (inverse of `tendon_force_mult`)
+pub fn inverse_tendon_force_mult(mult: f64) -> f64 {
+    let toe = constants::HILL_TENDON_STRAIN;
+    if mult <= 0_f64 {
+        0_f64
+    } else if mult <= 1_f64 {
+        toe * f64::sqrt(mult)
+    } else {
+        toe * (1_f64 + (mult - 1_f64) / 2_f64)
+    }
+}
+
+// tendon force for the strain: quadratic toe region and linear part after the strain
+// `HILL_TENDON_STRAIN`, on which tendon holds the maximal isometric force
+pub fn tendon_force_mult(strain: f64) -> f64 {
+    let toe = constants::HILL_TENDON_STRAIN;
+    if strain <= 0_f64 {
+        0_f64
+    } else if strain <= toe {
+        f64::powi(strain / toe, 2)
+    } else {
+        1_f64 + 2_f64 * (strain - toe) / toe
+    }
+}
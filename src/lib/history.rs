@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use super::prelude::*;
+
+// revisions are unique for all histories, so the new history of the opened session is
+// noticed too
+static REVISION: AtomicUsize = AtomicUsize::new(0);
+
+// state of the scene, which is restored by undo
+#[derive(Clone)]
+pub struct Snapshot {
+    pub muscles: Vec<Muscle>,
+    pub carcass: Carcass,
+    pub selected: usize,
+    pub activations: Vec<f64>,
+    pub force_model: ForceModel,
+    pub load: Option<Load>,
+    pub camera: Camera,
+    pub pivot: Pivot,
+    pub light_source: Point3d,
+}
+
+#[derive(Clone)]
+struct Entry {
+    name: String,
+    snapshot: Snapshot,
+    // time of the last change of the entry
+    time: Instant,
+}
+
+// Linear history of the states. A new state after undo drops the undone ones. States
+// with the same name recorded within `HISTORY_MERGE_MS` make one entry, so a held key or
+// the turn of the wheel is one step (a drag of the mouse is recorded on the release).
+#[derive(Clone, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    current: usize,
+    // last entry can take the next state with the same name
+    mergeable: bool,
+    // changes with every record and jump, so the list is rebuilt only when needed
+    revision: usize,
+}
+
+impl History {
+    pub fn record(&mut self, name: &str, snapshot: Snapshot) {
+        self.revision = next_revision();
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.current + 1);
+            let last = &mut self.entries[self.current];
+            if self.mergeable
+                && last.name == name
+                && last.time.elapsed().as_millis() < constants::HISTORY_MERGE_MS
+            {
+                last.snapshot = snapshot;
+                last.time = Instant::now();
+                return;
+            }
+        }
+        self.entries.push(Entry {
+            name: name.to_string(),
+            snapshot,
+            time: Instant::now(),
+        });
+        self.current = self.entries.len() - 1;
+        // the first state isn't changed by merging
+        self.mergeable = self.current > 0;
+    }
+
+    pub fn undo(&mut self) -> Option<Snapshot> {
+        if self.current == 0 {
+            return None;
+        }
+        self.jump(self.current - 1)
+    }
+
+    pub fn redo(&mut self) -> Option<Snapshot> {
+        self.jump(self.current + 1)
+    }
+
+    pub fn jump(&mut self, index: usize) -> Option<Snapshot> {
+        let snapshot = self.entries.get(index)?.snapshot.clone();
+        self.current = index;
+        self.mergeable = false;
+        self.revision = next_revision();
+        Some(snapshot)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+}
+
+fn next_revision() -> usize {
+    REVISION.fetch_add(1, Ordering::Relaxed) + 1
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::prelude::*;
+
+// action of the user independent of the input
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveBack,
+    MoveFront,
+    RotateLeft,
+    RotateRight,
+    RotateUp,
+    RotateDown,
+    RotateClockwise,
+    RotateUnclockwise,
+    ScaleUp,
+    ScaleDown,
+    ViewFront,
+    ViewSide,
+    ViewTop,
+    ViewAnatomical,
+    ZoomToFit,
+    Lengthen,
+    Shorten,
+    Simulate,
+    Undo,
+    Redo,
+    Help,
+}
+
+impl Command {
+    pub const ALL: [Command; 25] = [
+        Command::MoveLeft,
+        Command::MoveRight,
+        Command::MoveUp,
+        Command::MoveDown,
+        Command::MoveBack,
+        Command::MoveFront,
+        Command::RotateLeft,
+        Command::RotateRight,
+        Command::RotateUp,
+        Command::RotateDown,
+        Command::RotateClockwise,
+        Command::RotateUnclockwise,
+        Command::ScaleUp,
+        Command::ScaleDown,
+        Command::ViewFront,
+        Command::ViewSide,
+        Command::ViewTop,
+        Command::ViewAnatomical,
+        Command::ZoomToFit,
+        Command::Lengthen,
+        Command::Shorten,
+        Command::Simulate,
+        Command::Undo,
+        Command::Redo,
+        Command::Help,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::MoveLeft => "move left",
+            Command::MoveRight => "move right",
+            Command::MoveUp => "move up",
+            Command::MoveDown => "move down",
+            Command::MoveBack => "move back",
+            Command::MoveFront => "move front",
+            Command::RotateLeft => "rotate left",
+            Command::RotateRight => "rotate right",
+            Command::RotateUp => "rotate up",
+            Command::RotateDown => "rotate down",
+            Command::RotateClockwise => "rotate clockwise",
+            Command::RotateUnclockwise => "rotate counterclockwise",
+            Command::ScaleUp => "scale up",
+            Command::ScaleDown => "scale down",
+            Command::ViewFront => "front view",
+            Command::ViewSide => "side view",
+            Command::ViewTop => "top view",
+            Command::ViewAnatomical => "anatomical view",
+            Command::ZoomToFit => "zoom to fit",
+            Command::Lengthen => "lengthen muscle",
+            Command::Shorten => "shorten muscle",
+            Command::Simulate => "start/stop simulation",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::Help => "show/hide this help",
+        }
+    }
+}
+
+// key with modifiers, the key is a lowercase GDK keyval
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Shortcut {
+    keyval: u32,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl Shortcut {
+    // GDK key name with modifiers: `h`, `F1`, `Ctrl+Shift+Left`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = text.split('+').map(|part| part.trim()).collect();
+        let name = parts.pop().unwrap_or("");
+        let keyval = gdk::keyval_from_name(name);
+        if name.is_empty() || keyval == 0 || keyval == constants::VOID_KEYVAL {
+            return Err(format!("Unknown key: {}", text));
+        }
+
+        let mut shortcut = Self {
+            keyval: gdk::keyval_to_lower(keyval),
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => shortcut.ctrl = true,
+                "shift" => shortcut.shift = true,
+                "alt" => shortcut.alt = true,
+                _ => return Err(format!("Unknown modifier: {}", modifier)),
+            }
+        }
+        Ok(shortcut)
+    }
+
+    pub fn from_event(keyval: u32, state: gdk::ModifierType) -> Self {
+        Self {
+            keyval: gdk::keyval_to_lower(keyval),
+            ctrl: state.contains(gdk::ModifierType::CONTROL_MASK),
+            shift: state.contains(gdk::ModifierType::SHIFT_MASK),
+            alt: state.contains(gdk::ModifierType::MOD1_MASK),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let mut text = String::new();
+        if self.ctrl {
+            text += "Ctrl+";
+        }
+        if self.shift {
+            text += "Shift+";
+        }
+        if self.alt {
+            text += "Alt+";
+        }
+        match gdk::keyval_name(self.keyval) {
+            Some(name) => text + name.as_str(),
+            None => text + &format!("{:#x}", self.keyval),
+        }
+    }
+}
+
+pub struct Keybindings {
+    bindings: Vec<(Shortcut, Command)>,
+}
+
+impl Keybindings {
+    // table of the file: key names are mapped to commands
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let reader = File::open(path).map_err(|err| format!("Can't open keybindings!\n{}", err))?;
+        let table: BTreeMap<String, Command> = serde_yaml::from_reader(BufReader::new(reader))
+            .map_err(|err| format!("Bad keybindings!\n{}", err))?;
+        let bindings = table
+            .iter()
+            .map(|(key, &command)| Ok((Shortcut::parse(key)?, command)))
+            .collect::<Result<_, String>>()
+            .map_err(|err| format!("Bad keybindings!\n{}", err))?;
+        Ok(Self { bindings })
+    }
+
+    // Command of the shortcut. Shift is ignored, if the key isn't bound with it, so
+    // letters are case insensitive.
+    pub fn command(&self, shortcut: Shortcut) -> Option<Command> {
+        let find = |shortcut| {
+            self.bindings
+                .iter()
+                .find(|(s, _)| *s == shortcut)
+                .map(|(_, command)| *command)
+        };
+        find(shortcut).or_else(|| {
+            find(Shortcut {
+                shift: false,
+                ..shortcut
+            })
+        })
+    }
+
+    // shortcuts of the command, the first one is shown on its button
+    pub fn shortcuts(&self, command: Command) -> Vec<Shortcut> {
+        let mut shortcuts: Vec<Shortcut> = self
+            .bindings
+            .iter()
+            .filter(|(_, c)| *c == command)
+            .map(|(s, _)| *s)
+            .collect();
+        shortcuts.sort();
+        shortcuts
+    }
+
+    // bound commands with their shortcuts
+    pub fn help(&self) -> String {
+        Command::ALL
+            .iter()
+            .filter_map(|&command| {
+                let names: Vec<String> = self.shortcuts(command).iter().map(|s| s.name()).collect();
+                if names.is_empty() {
+                    return None;
+                }
+                Some(format!("{:<16}{}", names.join(", "), command.description()))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            bindings: constants::DEFAULT_KEYBINDINGS
+                .iter()
+                .map(|&(key, command)| (Shortcut::parse(key).unwrap(), command))
+                .collect(),
+        }
+    }
+}
+
+// keybindings file beside the config or the default table, if there is no file
+pub fn read_keybindings(config_path: &Path) -> Result<Keybindings, String> {
+    let path = config_path.with_file_name(constants::KEYS_FILE);
+    if !path.exists() {
+        return Ok(Keybindings::default());
+    }
+    Keybindings::read(path)
+}
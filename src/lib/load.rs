@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+// mass (kg) in the hand, held by the muscle with index `muscle`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Load {
+    pub mass: f64,
+    pub muscle: usize,
+}
+
+#[derive(Clone)]
+pub struct LoadResult {
+    // force (N) and activation of the holding muscle needed for static equilibrium, there
+    // is no activation if the muscle pulls in the direction of the load
+    pub force: f64,
+    pub activation: Option<f64>,
+    // false if the muscle can't produce the needed force
+    pub holdable: bool,
+    // elongation of the tendon, by which the belly of the muscle is shortened
+    pub stretch: f64,
+    // maximal growth of the node radius
+    pub bulge: f64,
+    // holding muscle with the shortened belly
+    pub muscle: Muscle,
+}
+
+// Torque (N * m, positive bends the arm) of gravity applied to the second bone and the
+// load at its distal end. The first bone hangs vertically, so the lever is proportional
+// to the sine of the angle in the elbow.
+pub fn gravity_torque(carcass: &Carcass, angle: f64, mass: f64, forearm_mass: f64) -> f64 {
+    let len = carcass.forearm_len() / constants::UNITS_PER_METER;
+    -(mass * len + forearm_mass * len / 2_f64) * constants::GRAVITY * f64::sin(angle)
+}
+
+// Finds the force and the activation of the holding muscle, with which the joint is in
+// static equilibrium at the current angle. Other muscles keep their activations.
+pub fn hold(
+    load: &Load,
+    muscles: &[SceneMuscle],
+    carcass: &Carcass,
+    activations: &[f64],
+    model: ForceModel,
+    forearm_mass: f64,
+) -> LoadResult {
+    let angle = carcass.angle();
+    let holder = &muscles[load.muscle];
+    let hill = holder.hill();
+    let mtu_len = carcass.muscle_len(holder.attachment());
+
+    let forces = static_forces(muscles, carcass, angle, activations, model);
+    let others: f64 = joint_mechanics(muscles, carcass, angle, &forces)
+        .muscles
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != load.muscle)
+        .map(|(_, m)| m.torque)
+        .sum();
+    let arm = carcass.moment_arm(holder.attachment()) / constants::UNITS_PER_METER;
+    // torque of the holding muscle, which balances the load and other muscles
+    let needed = -(gravity_torque(carcass, angle, load.mass, forearm_mass) + others);
+    let force = if relative_eq!(needed, 0_f64) {
+        0_f64
+    } else if relative_eq!(arm, 0_f64) || needed.signum() != arm.signum() {
+        // muscle only pulls, so its torque has the sign of the moment arm
+        return LoadResult {
+            force: 0_f64,
+            activation: None,
+            holdable: false,
+            stretch: 0_f64,
+            bulge: 0_f64,
+            muscle: holder.muscle().lock().unwrap().clone(),
+        };
+    } else {
+        needed / arm
+    };
+
+    // force grows with the activation in both models
+    let (mut low, mut high) = (0_f64, 1_f64);
+    let holdable = model.force(hill, high, mtu_len) >= force;
+    if model.force(hill, low, mtu_len) >= force {
+        high = low;
+    } else if holdable {
+        for _ in 0..constants::HILL_ITERATIONS {
+            let mid = (low + high) / 2_f64;
+            if model.force(hill, mid, mtu_len) < force {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+    }
+    let activation = high;
+
+    let stretch = hill.tendon_slack_len
+        * inverse_tendon_force_mult(
+            force.min(model.force(hill, activation, mtu_len)) / hill.max_force,
+        );
+    let mut muscle = holder.muscle().lock().unwrap().clone();
+    let before = muscle.radiuses().to_vec();
+    // belly is left as is if it can't be shortened so much
+    let _ = muscle.deform(-stretch);
+    let bulge = muscle
+        .radiuses()
+        .iter()
+        .zip(before.iter())
+        .map(|(new, old)| new - old)
+        .fold(0_f64, f64::max);
+
+    LoadResult {
+        force,
+        activation: Some(activation),
+        holdable,
+        stretch,
+        bulge,
+        muscle,
+    }
+}
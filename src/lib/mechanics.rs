@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::prelude::*;
+
+// model of the static (isometric) muscle force
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForceModel {
+    // force of the tendon in equilibrium with the Hill-type fibers
+    Hill,
+    // force is proportional to the activation
+    Linear,
+}
+
+impl ForceModel {
+    pub fn force(&self, hill: &HillParams, activation: f64, mtu_len: f64) -> f64 {
+        match self {
+            ForceModel::Hill => {
+                let fiber_len = hill.equilibrium_fiber_len(activation, mtu_len);
+                hill.tendon_force(mtu_len - fiber_len)
+            }
+            ForceModel::Linear => activation * hill.max_force,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MuscleMechanics {
+    pub len: f64,
+    // model units, positive for flexors
+    pub moment_arm: f64,
+    // N
+    pub force: f64,
+    // N * m, positive torque bends the arm
+    pub torque: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct JointMechanics {
+    // angle in the elbow
+    pub angle: f64,
+    pub muscles: Vec<MuscleMechanics>,
+    pub torque: f64,
+}
+
+// mechanics of the joint at the angle `angle` with given muscle forces
+pub fn joint_mechanics(
+    muscles: &[SceneMuscle],
+    carcass: &Carcass,
+    angle: f64,
+    forces: &[f64],
+) -> JointMechanics {
+    let muscles: Vec<MuscleMechanics> = muscles
+        .iter()
+        .zip(forces.iter())
+        .map(|(m, &force)| {
+            let moment_arm = carcass.moment_arm_at(m.attachment(), angle);
+            MuscleMechanics {
+                len: carcass.muscle_len_at(m.attachment(), angle),
+                moment_arm,
+                force,
+                torque: force * moment_arm / constants::UNITS_PER_METER,
+            }
+        })
+        .collect();
+    let torque = muscles.iter().map(|m| m.torque).sum();
+
+    JointMechanics {
+        angle,
+        muscles,
+        torque,
+    }
+}
+
+pub fn static_forces(
+    muscles: &[SceneMuscle],
+    carcass: &Carcass,
+    angle: f64,
+    activations: &[f64],
+    model: ForceModel,
+) -> Vec<f64> {
+    muscles
+        .iter()
+        .zip(activations.iter())
+        .map(|(m, &a)| model.force(m.hill(), a, carcass.muscle_len_at(m.attachment(), angle)))
+        .collect()
+}
+
+// mechanics in `samples` angles evenly spaced over the whole range of the joint
+pub fn sweep(
+    muscles: &[SceneMuscle],
+    carcass: &Carcass,
+    activations: &[f64],
+    model: ForceModel,
+    samples: usize,
+) -> Vec<JointMechanics> {
+    let (min, max) = carcass.angle_range();
+    (0..samples)
+        .map(|i| min + (max - min) * i as f64 / (samples - 1) as f64)
+        .map(|angle| {
+            let forces = static_forces(muscles, carcass, angle, activations, model);
+            joint_mechanics(muscles, carcass, angle, &forces)
+        })
+        .collect()
+}
+
+pub fn write_sweep_csv<P: AsRef<Path>>(
+    path: P,
+    names: &[String],
+    rows: &[JointMechanics],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write!(writer, "angle")?;
+    for name in names.iter() {
+        write!(writer, ",{0}_len,{0}_moment_arm,{0}_force,{0}_torque", name)?;
+    }
+    writeln!(writer, ",torque")?;
+
+    for row in rows.iter() {
+        write!(writer, "{}", row.angle.to_degrees())?;
+        for m in row.muscles.iter() {
+            write!(
+                writer,
+                ",{},{},{},{}",
+                m.len, m.moment_arm, m.force, m.torque
+            )?;
+        }
+        writeln!(writer, ",{}", row.torque)?;
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use super::prelude::*;
+
+// measurements of the current state of one muscle
+#[derive(Clone, Debug)]
+pub struct MuscleMetrics {
+    pub len: f64,
+    pub volume: f64,
+    pub max_radius: f64,
+    pub mean_radius: f64,
+    // cross-sectional areas of the nodes
+    pub areas: Vec<f64>,
+    // angle in the elbow
+    pub angle: f64,
+    // relative change of the volume from the initial state
+    pub volume_drift: f64,
+}
+
+impl MuscleMetrics {
+    pub fn new(muscle: &Muscle, angle: f64, initial_volume: f64) -> Self {
+        let radiuses = muscle.radiuses();
+        let volume = muscle.volume();
+        Self {
+            len: muscle.len(),
+            volume,
+            max_radius: radiuses.iter().cloned().fold(0_f64, f64::max),
+            mean_radius: radiuses.iter().sum::<f64>() / radiuses.len() as f64,
+            areas: radiuses
+                .iter()
+                .map(|rad| std::f64::consts::PI * rad * rad)
+                .collect(),
+            angle,
+            volume_drift: (volume - initial_volume) / initial_volume,
+        }
+    }
+}
+
+// metrics of one muscle recorded on every change of the state
+#[derive(Clone)]
+pub struct MetricsRecorder {
+    muscle: usize,
+    start: Instant,
+    // (seconds from the start, metrics)
+    rows: Vec<(f64, MuscleMetrics)>,
+}
+
+impl MetricsRecorder {
+    pub fn new(muscle: usize) -> Self {
+        Self {
+            muscle,
+            start: Instant::now(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn muscle(&self) -> usize {
+        self.muscle
+    }
+
+    pub fn record(&mut self, metrics: MuscleMetrics) {
+        self.rows
+            .push((self.start.elapsed().as_secs_f64(), metrics));
+    }
+
+    // number of area columns is taken by the muscle with most nodes, the rest are empty
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let nodes = self
+            .rows
+            .iter()
+            .map(|(_, m)| m.areas.len())
+            .max()
+            .unwrap_or(0);
+
+        write!(
+            writer,
+            "time,angle,len,volume,volume_drift,max_radius,mean_radius"
+        )?;
+        for i in 0..nodes {
+            write!(writer, ",area_{}", i)?;
+        }
+        writeln!(writer)?;
+
+        for (time, m) in self.rows.iter() {
+            write!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                time,
+                m.angle.to_degrees(),
+                m.len,
+                m.volume,
+                m.volume_drift,
+                m.max_radius,
+                m.mean_radius
+            )?;
+            for i in 0..nodes {
+                match m.areas.get(i) {
+                    Some(area) => write!(writer, ",{}", area)?,
+                    None => write!(writer, ",")?,
+                }
+            }
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+}
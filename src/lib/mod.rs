@@ -1,29 +1,70 @@
+mod admissible;
+mod camera;
 mod carcass;
 mod cg;
 pub mod constants;
+mod contact;
 mod controller;
+mod deformation;
+mod experiment;
+mod fitting;
+mod hill;
+mod history;
+mod keybindings;
+mod load;
+mod mechanics;
+mod metrics;
 mod muscle;
+mod picking;
 mod primitives;
+mod scene;
+mod session;
+mod simulation;
 mod stubs;
 mod ui;
 mod utils;
+mod validation;
 
 pub mod prelude {
-    pub use super::cg::{clear_buffers, flush, transform_and_add};
+    pub use super::admissible::{explore, AdmissibleRange};
+    pub use super::camera::{bounding_box, Camera, Pivot, ViewPreset};
+    pub use super::cg::{clear_buffers, draw_disc, draw_line, flush, object_at, transform_and_add};
     pub use super::constants;
-    pub use super::constants::keys;
+    pub use super::contact::{find_contacts, flatten_display_rings, Contact};
     pub use super::controller::Controller;
-    pub use super::muscle::{MOParams, Muscle, MuscleOperation};
+    pub use super::deformation::{DeformationKind, DeformationModel, KeyframeModel};
+    pub use super::experiment::compare_command;
+    pub use super::fitting::{fit_command, muscle_index, pose_radiuses, rmse, Measurements};
+    pub use super::hill::{inverse_tendon_force_mult, HillParams};
+    pub use super::history::{History, Snapshot};
+    pub use super::keybindings::{read_keybindings, Command, Keybindings, Shortcut};
+    pub use super::load::{hold, Load, LoadResult};
+    pub use super::mechanics::{
+        joint_mechanics, static_forces, sweep, write_sweep_csv, ForceModel, JointMechanics,
+    };
+    pub use super::metrics::{MetricsRecorder, MuscleMetrics};
+    pub use super::muscle::{
+        DeformError, MOParams, Muscle, MuscleOperation, SmoothFilter, SmoothParams,
+    };
+    pub use super::picking::{light_marker, nearest_segment, segment_distance, Pick};
     pub use super::primitives::{
-        Axis, CenterTransformator, IntYPoint3d, Matrix4, Point3d, Section, Transformator, Vec2d,
-        Vec3d,
+        Axis, IntYPoint3d, Matrix4, Point3d, Quaternion, Section, Transformator, Vec2d, Vec3d,
     };
-    pub use super::ui::{build_ui, process_key};
+    pub use super::ui::{build_ui, parse_ui_args};
     pub use super::utils::{
-        add_uv_sphere, angle_from_triangle, cycle_extend, read_from_config, rotate_intersections,
-        solve_quad_eq, CarcassConfig, Config, MuscleConfig,
+        add_uv_sphere, angle_from_triangle, cycle_extend, default_config_path, expand_bracket,
+        read_config, rotate_intersections, solve_bracketed, Config, MuscleConfig,
     };
     //pub use super::stubs::{ dy_stub };
-    pub use super::carcass::Carcass;
+    pub use super::carcass::{Attachment, Carcass};
+    pub use super::scene::{
+        build_carcass, build_scene, config_attachment, keyframe_model, SceneMuscle,
+    };
+    pub use super::session::{session_command, SavedView, Session};
+    pub use super::simulation::{Excitation, Simulation, SimulationConfig};
+    pub use super::validation::{
+        validate_config, validate_joint, validate_positive, validate_profile, validate_radiuses,
+        validate_triangle, ValidationError,
+    };
     pub use gdk_pixbuf::Pixbuf;
 }
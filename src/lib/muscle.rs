@@ -1,30 +1,57 @@
 use super::prelude::*;
 use std::vec::Vec;
 
+#[derive(Clone)]
 pub struct Muscle {
     radiuses: Vec<f64>,
     grow_mults: Vec<f64>,
     dx: f64,
     min_dx: f64,
     max_dx: f64,
+    tolerance: f64,
+    model: DeformationKind,
+    // measured profiles replace the deformation model
+    keyframes: Option<KeyframeModel>,
 }
 
 impl Muscle {
-    pub fn new(radiuses: Vec<f64>, grow_mults: Vec<f64>, len: f64) -> Self {
+    pub fn new(
+        radiuses: Vec<f64>,
+        grow_mults: Vec<f64>,
+        len: f64,
+    ) -> Result<Self, ValidationError> {
+        validate_profile(&radiuses, &grow_mults)?;
+        validate_positive("len", len, false)?;
         let dx = len / (radiuses.len() - 1) as f64;
-        Self {
+        Ok(Self {
             radiuses,
             grow_mults,
             dx,
             min_dx: dx * constants::MIN_PART,
             max_dx: dx * constants::MAX_PART,
-        }
+            tolerance: constants::VOLUME_TOLERANCE,
+            model: DeformationKind::default(),
+            keyframes: None,
+        })
     }
 
     pub fn radiuses(&self) -> &[f64] {
         &self.radiuses
     }
 
+    pub fn grow_mults(&self) -> &[f64] {
+        &self.grow_mults
+    }
+
+    pub fn dx(&self) -> f64 {
+        self.dx
+    }
+
+    // radius of the muscle on the distance `x` from its beginning
+    pub fn radius_at(&self, x: f64) -> f64 {
+        interpolate(&self.radiuses, self.dx, x)
+    }
+
     fn get_angle(&self, i: usize) -> f64 {
         let last = self.radiuses.len() - 1;
         match i {
@@ -108,6 +135,10 @@ impl Muscle {
         (points, normal2points)
     }
 
+    pub fn volume(&self) -> f64 {
+        std::f64::consts::PI * self.find_volume()
+    }
+
     // volume divided by pi
     fn find_volume(&self) -> f64 {
         let mut res = 0_f64;
@@ -120,62 +151,108 @@ impl Muscle {
         res * self.dx
     }
 
-    fn find_a(&self) -> f64 {
-        let mut res = 0_f64;
-
-        for mults in self.grow_mults.windows(2) {
-            res += mults[1] * mults[1] - 5_f64 * mults[0] * mults[1] + 7_f64 * mults[0] * mults[0];
-        }
-
-        res / 3_f64
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        self.tolerance = tolerance;
     }
 
-    fn find_b(&self) -> f64 {
-        let mut res = 0_f64;
+    pub fn model(&self) -> DeformationKind {
+        self.model
+    }
 
-        for (rads, mults) in self.radiuses.windows(2).zip(self.grow_mults.windows(2)) {
-            res += mults[1] * rads[0] + mults[0] * rads[1];
-        }
+    pub fn set_model(&mut self, model: DeformationKind) {
+        self.model = model;
+    }
 
-        res
+    pub fn keyframed(&self) -> bool {
+        self.keyframes.is_some()
     }
 
-    fn find_c(&self, g: f64) -> f64 {
-        let mut res = 0_f64;
+    pub fn set_keyframes(&mut self, keyframes: Option<KeyframeModel>) {
+        self.keyframes = keyframes;
+    }
 
-        for rads in self.radiuses.windows(2) {
-            res += 1_f64 / 3_f64 * f64::powi(rads[1] - rads[0], 2) + rads[0] * rads[1];
+    // Replaces the profile and the length keeping the model and keyframes; `len_range` is
+    // set as it was saved and must contain the length.
+    pub fn set_state(
+        &mut self,
+        radiuses: Vec<f64>,
+        grow_mults: Vec<f64>,
+        len: f64,
+        (min, max): (f64, f64),
+    ) -> Result<(), ValidationError> {
+        validate_profile(&radiuses, &grow_mults)?;
+        validate_positive("len", len, false)?;
+        validate_positive("min len", min, false)?;
+        validate_positive("max len", max, false)?;
+        if len < min || len > max {
+            return Err(ValidationError::MuscleLength { len, min, max });
         }
-
-        res - g
+        let nodes = (radiuses.len() - 1) as f64;
+        self.radiuses = radiuses;
+        self.grow_mults = grow_mults;
+        self.dx = len / nodes;
+        self.min_dx = min / nodes;
+        self.max_dx = max / nodes;
+        Ok(())
     }
 
-    fn update_radiuses(&mut self, dy: f64) {
-        for (rad, mult) in self.radiuses.iter_mut().zip(self.grow_mults.iter()) {
-            *rad += mult * dy;
-        }
+    // lengths allowed by `MIN_PART` and `MAX_PART`
+    pub fn len_range(&self) -> (f64, f64) {
+        let nodes = (self.radiuses.len() - 1) as f64;
+        (self.min_dx * nodes, self.max_dx * nodes)
     }
 
-    pub fn deform(&mut self, diff: f64) {
+    pub fn check_diff(&self, diff: f64) -> bool {
         let new_dx = self.dx + diff / (self.radiuses.len() - 1) as f64;
-        if new_dx < self.min_dx || new_dx > self.max_dx {
-            return;
-        }
-
-        let g2 = self.find_volume() / new_dx;
-
-        let a = self.find_a();
-        let b = self.find_b();
-        let c = self.find_c(g2);
+        new_dx >= self.min_dx && new_dx <= self.max_dx
+    }
 
-        let dy = solve_quad_eq(a, b, c);
-        if let Some(dy) = dy.1 {
-            self.update_radiuses(dy);
-            self.dx = new_dx;
+    // Changes the length of the muscle by `diff` keeping its volume, radiuses are found
+    // by the keyframes or by the deformation model of the muscle. The muscle is not
+    // changed on failure.
+    pub fn deform(&mut self, diff: f64) -> Result<DeformReport, DeformError> {
+        let nodes = (self.radiuses.len() - 1) as f64;
+        if !self.check_diff(diff) {
+            let (min, max) = self.len_range();
+            return Err(DeformError::OutOfRange {
+                len: self.len() + diff,
+                min,
+                max,
+            });
         }
+        let new_dx = self.dx + diff / nodes;
+
+        let volume = self.find_volume();
+        let model: &dyn DeformationModel = match &self.keyframes {
+            Some(keyframes) => keyframes,
+            None => self.model.model(),
+        };
+        let (radiuses, iterations) = model.deform(
+            &self.radiuses,
+            &self.grow_mults,
+            self.dx,
+            new_dx,
+            self.tolerance,
+        )?;
+        validate_radiuses(&radiuses).map_err(DeformError::BadProfile)?;
+        let old_radiuses = std::mem::replace(&mut self.radiuses, radiuses);
+        let old_dx = std::mem::replace(&mut self.dx, new_dx);
+
+        let volume_error = (self.find_volume() - volume).abs() / volume;
+        if volume_error > self.tolerance {
+            self.radiuses = old_radiuses;
+            self.dx = old_dx;
+            return Err(DeformError::NotConverged {
+                error: volume_error,
+            });
+        }
+
+        Ok(DeformReport {
+            iterations,
+            volume_error,
+        })
     }
 
-    #[allow(dead_code)]
     pub fn len(&self) -> f64 {
         self.dx * (self.radiuses.len() - 1) as f64
     }
@@ -191,16 +268,38 @@ impl Muscle {
         Ok((self.radiuses[pos], self.grow_mults[pos]))
     }
 
-    pub fn restruct(&mut self, mo: MuscleOperation) -> Result<(), String> {
+    // Applies the operation and checks the new profile, the muscle is not changed on
+    // failure.
+    pub fn restruct(&mut self, mo: MuscleOperation) -> Result<(), ValidationError> {
+        let backup = self.clone();
+        let res = self
+            .apply(mo)
+            .and_then(|_| validate_profile(&self.radiuses, &self.grow_mults));
+        if res.is_err() {
+            *self = backup;
+        }
+        res
+    }
+
+    fn apply(&mut self, mo: MuscleOperation) -> Result<(), ValidationError> {
         let len = self.dx * (self.radiuses.len() - 1) as f64;
+        // profile would be replaced by the keyframes on the next deformation
+        if self.keyframes.is_some() {
+            return Err(ValidationError::Keyframed);
+        }
         match mo {
             MuscleOperation::Del(pos) => {
-                if pos > self.radiuses.len() - 1 || self.radiuses.len() < 3 {
-                    return Err(format!(
-                        "Can't delete!\npos: {};\nnumber of nodes: {}",
+                if pos > self.radiuses.len() - 1 {
+                    return Err(ValidationError::BadPosition {
+                        op: "delete",
                         pos,
-                        self.radiuses.len()
-                    ));
+                        nodes: self.radiuses.len(),
+                    });
+                }
+                if self.radiuses.len() < 3 {
+                    return Err(ValidationError::TooFewNodes {
+                        nodes: self.radiuses.len() - 1,
+                    });
                 }
                 self.radiuses.remove(pos);
                 self.grow_mults.remove(pos);
@@ -213,23 +312,85 @@ impl Muscle {
             }
             MuscleOperation::Mod(MOParams { pos, rad, gm }) => {
                 if pos > self.radiuses.len() - 1 {
-                    return Err(format!(
-                        "Can't modify!\npos: {};\nnumber of nodes: {}",
+                    return Err(ValidationError::BadPosition {
+                        op: "modify",
                         pos,
-                        self.radiuses.len()
-                    ));
+                        nodes: self.radiuses.len(),
+                    });
                 }
                 self.radiuses[pos] = rad;
                 self.grow_mults[pos] = gm;
                 Ok(())
             }
+            MuscleOperation::SoftMod(MOParams { pos, rad, gm }, falloff) => {
+                if pos > self.radiuses.len() - 1 {
+                    return Err(ValidationError::BadPosition {
+                        op: "modify",
+                        pos,
+                        nodes: self.radiuses.len(),
+                    });
+                }
+                validate_positive("falloff", falloff, true)?;
+                let (drad, dgm) = (rad - self.radiuses[pos], gm - self.grow_mults[pos]);
+                for (i, (rad, gm)) in self
+                    .radiuses
+                    .iter_mut()
+                    .zip(self.grow_mults.iter_mut())
+                    .enumerate()
+                {
+                    let w = soft_weight((i as f64 - pos as f64).abs(), falloff);
+                    *rad = (*rad + drad * w).max(0_f64);
+                    *gm += dgm * w;
+                }
+                Ok(())
+            }
+            MuscleOperation::Smooth(SmoothParams {
+                filter,
+                strength,
+                grow_mults,
+            }) => {
+                validate_positive("strength", strength, true)?;
+                if grow_mults {
+                    self.grow_mults = smooth(&self.grow_mults, filter, strength);
+                } else {
+                    self.radiuses = smooth(&self.radiuses, filter, strength);
+                }
+                Ok(())
+            }
+            MuscleOperation::Resample(n) => {
+                if n < 2 {
+                    return Err(ValidationError::TooFewNodes { nodes: n });
+                }
+                let volume = self.find_volume();
+                let dx = len / (n - 1) as f64;
+                let positions = (0..n).map(|i| dx * i as f64);
+                self.radiuses = positions
+                    .clone()
+                    .map(|x| interpolate(&self.radiuses, self.dx, x))
+                    .collect();
+                self.grow_mults = positions
+                    .map(|x| interpolate(&self.grow_mults, self.dx, x))
+                    .collect();
+
+                self.dx = dx;
+                self.min_dx = self.dx * constants::MIN_PART;
+                self.max_dx = self.dx * constants::MAX_PART;
+
+                // linear interpolation cuts the bulges of the profile
+                let k = f64::sqrt(volume / self.find_volume());
+                for rad in self.radiuses.iter_mut() {
+                    *rad *= k;
+                }
+
+                Ok(())
+            }
             MuscleOperation::Add(MOParams { pos, rad, gm }) => {
                 if pos > self.radiuses.len() {
-                    return Err(format!(
-                        "Can't add!\npos: {};\nlen: {}",
+                    return Err(ValidationError::BadPosition {
+                        op: "add",
                         pos,
-                        self.radiuses.len()
-                    ));
+                        nodes: self.radiuses.len(),
+                    });
                 }
                 self.radiuses.insert(pos, rad);
                 self.grow_mults.insert(pos, gm);
@@ -314,10 +475,128 @@ impl Muscle {
     }
 }
 
+// result of the successful deformation
+#[derive(Clone, Copy, Debug)]
+pub struct DeformReport {
+    pub iterations: usize,
+    // relative error of the volume
+    pub volume_error: f64,
+}
+
+#[derive(Clone, Debug)]
+pub enum DeformError {
+    // length is out of [min, max]
+    OutOfRange { len: f64, min: f64, max: f64 },
+    // all grow multipliers are zero
+    NoGrowth,
+    // volume can't be kept with non-negative radiuses
+    NoSolution,
+    // solver reached the iteration limit
+    NotConverged { error: f64 },
+    // model gave negative or not finite radiuses
+    BadProfile(ValidationError),
+}
+
+impl std::fmt::Display for DeformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeformError::OutOfRange { len, min, max } => write!(
+                f,
+                "Can't deform!\nlen: {:.2};\nallowed: [{:.2}, {:.2}]",
+                len, min, max
+            ),
+            DeformError::NoGrowth => write!(f, "Can't keep volume: all grow multipliers are zero"),
+            DeformError::NoSolution => {
+                write!(f, "Can't keep volume: radiuses would become negative")
+            }
+            DeformError::NotConverged { error } => {
+                write!(f, "Solver didn't converge: volume error {:e}", error)
+            }
+            DeformError::BadProfile(err) => write!(f, "Bad deformed profile: {}", err),
+        }
+    }
+}
+
+// linear interpolation of values in nodes with step `dx` on the distance `x`
+fn interpolate(values: &[f64], dx: f64, x: f64) -> f64 {
+    let last = values.len() - 1;
+    let pos = (x / dx).max(0_f64).min(last as f64);
+    let i = usize::min(pos as usize, last - 1);
+    let t = pos - i as f64;
+    values[i] * (1_f64 - t) + values[i + 1] * t
+}
+
+// weight of the change of the node on the distance `dist` (in nodes) from the edited one
+fn soft_weight(dist: f64, falloff: f64) -> f64 {
+    if dist == 0_f64 {
+        1_f64
+    } else if dist >= falloff {
+        0_f64
+    } else {
+        f64::powi(1_f64 - f64::powi(dist / falloff, 2), 2)
+    }
+}
+
+// smoothed profile, the end nodes are kept
+fn smooth(values: &[f64], filter: SmoothFilter, strength: f64) -> Vec<f64> {
+    let last = values.len() - 1;
+    let mut res = values.to_vec();
+    if strength == 0_f64 || last < 2 {
+        return res;
+    }
+
+    match filter {
+        // `strength` is the standard deviation in nodes, edge values are repeated
+        SmoothFilter::Gaussian => {
+            let half = f64::ceil(3_f64 * strength) as isize;
+            for (i, value) in res.iter_mut().enumerate().take(last).skip(1) {
+                let (mut sum, mut weights) = (0_f64, 0_f64);
+                for d in -half..=half {
+                    let j = (i as isize + d).clamp(0, last as isize) as usize;
+                    let w = f64::exp(-f64::powi(d as f64 / strength, 2) / 2_f64);
+                    sum += values[j] * w;
+                    weights += w;
+                }
+                *value = sum / weights;
+            }
+        }
+        // `strength` is the number of passes, the fractional part makes the last one weaker
+        SmoothFilter::Laplacian => {
+            let passes = f64::ceil(strength);
+            let lambda = strength / passes;
+            for _ in 0..passes as usize {
+                let prev = res.clone();
+                for i in 1..last {
+                    res[i] += lambda * ((prev[i - 1] + prev[i + 1]) / 2_f64 - prev[i]);
+                }
+            }
+        }
+    }
+    res
+}
+
 pub enum MuscleOperation {
     Add(MOParams),
     Mod(MOParams),
     Del(usize),
+    // evenly spaced nodes with the same length and volume
+    Resample(usize),
+    // neighbours of the node are changed too with given falloff (in nodes)
+    SoftMod(MOParams, f64),
+    Smooth(SmoothParams),
+}
+
+impl MuscleOperation {
+    // name of the operation in the history
+    pub fn name(&self) -> &'static str {
+        match self {
+            MuscleOperation::Add(_) => "add node",
+            MuscleOperation::Mod(_) | MuscleOperation::SoftMod(_, _) => "modify node",
+            MuscleOperation::Del(_) => "delete node",
+            MuscleOperation::Resample(_) => "resample",
+            MuscleOperation::Smooth(_) => "smooth",
+        }
+    }
 }
 
 pub struct MOParams {
@@ -331,3 +610,26 @@ impl MOParams {
         Self { pos, rad, gm }
     }
 }
+
+#[derive(Clone, Copy, Debug)]
+pub enum SmoothFilter {
+    Gaussian,
+    Laplacian,
+}
+
+pub struct SmoothParams {
+    filter: SmoothFilter,
+    strength: f64,
+    // smooth grow multipliers instead of radiuses
+    grow_mults: bool,
+}
+
+impl SmoothParams {
+    pub fn new(filter: SmoothFilter, strength: f64, grow_mults: bool) -> Self {
+        Self {
+            filter,
+            strength,
+            grow_mults,
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use super::prelude::*;
+
+// object of the scene chosen by the click on the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pick {
+    // (muscle, node)
+    Node(usize, usize),
+    // 0 - humerus, 1 - forearm
+    Bone(usize),
+    Light,
+}
+
+impl Pick {
+    pub fn describe(&self, names: &[String]) -> String {
+        match self {
+            Pick::Node(muscle, node) => format!("{}: node {}", names[*muscle], node),
+            Pick::Bone(0) => "humerus".to_string(),
+            Pick::Bone(_) => "forearm".to_string(),
+            Pick::Light => "light source".to_string(),
+        }
+    }
+}
+
+// Position of the light marker on the screen. The light source is given in the screen
+// space, if it is out of the screen, the marker is moved to its border towards the center.
+pub fn light_marker(light_source: &Point3d) -> Point3d {
+    let center = (
+        (constants::WIDTH / 2) as f64,
+        (constants::HEIGHT / 2) as f64,
+    );
+    let (dx, dy) = (light_source.x - center.0, light_source.y - center.1);
+    let (max_x, max_y) = (
+        center.0 - constants::LIGHT_MARKER_MARGIN,
+        center.1 - constants::LIGHT_MARKER_MARGIN,
+    );
+    let mut t = 1_f64;
+    if dx.abs() > max_x {
+        t = t.min(max_x / dx.abs());
+    }
+    if dy.abs() > max_y {
+        t = t.min(max_y / dy.abs());
+    }
+    Point3d::new(center.0 + dx * t, center.1 + dy * t, light_source.z)
+}
+
+// distance from the point to the segment
+pub fn segment_distance(p: &Point3d, (a, b): &(Point3d, Point3d)) -> f64 {
+    let ab = Vec3d::from_pts(a, b);
+    let ap = Vec3d::from_pts(a, p);
+    let len2 = ab.scalar_mul(&ab);
+    let t = if len2 < f64::EPSILON {
+        0_f64
+    } else {
+        (ap.scalar_mul(&ab) / len2).clamp(0_f64, 1_f64)
+    };
+    Vec3d::new(ap.x - ab.x * t, ap.y - ab.y * t, ap.z - ab.z * t).len()
+}
+
+// index of the segment nearest to the point
+pub fn nearest_segment(p: &Point3d, segments: &[(Point3d, Point3d)]) -> Option<usize> {
+    (0..segments.len()).min_by(|&i, &j| {
+        segment_distance(p, &segments[i])
+            .partial_cmp(&segment_distance(p, &segments[j]))
+            .unwrap()
+    })
+}
@@ -1,7 +1,11 @@
-use std::ops::MulAssign;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
+use std::ops::{Mul, MulAssign};
 
-#[derive(Clone, Copy, Debug)]
+use super::constants;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct Point3d {
     pub x: f64,
     pub y: f64,
@@ -84,6 +88,14 @@ impl Vec3d {
     pub fn scalar_mul(&self, other: &Self) -> f64 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
+
+    pub fn vector_mul(&self, other: &Self) -> Self {
+        Self {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
 }
 
 impl Default for Vec3d {
@@ -92,6 +104,7 @@ impl Default for Vec3d {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct Vec2d {
     pub x: f64,
     pub y: f64,
@@ -103,12 +116,170 @@ impl Vec2d {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum Axis {
     X,
     Y,
     Z,
 }
 
+impl Axis {
+    pub fn vector(&self) -> Vec3d {
+        match self {
+            Axis::X => Vec3d::new(1_f64, 0_f64, 0_f64),
+            Axis::Y => Vec3d::new(0_f64, 1_f64, 0_f64),
+            Axis::Z => Vec3d::new(0_f64, 0_f64, 1_f64),
+        }
+    }
+}
+
+// Unit quaternion of the orientation. Rotations are in the same direction as
+// `Transformator::rotate`, and the product `b * a` rotates by `a` first (like the
+// product of the matrices `a * b`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn identity() -> Self {
+        Self::new(1_f64, 0_f64, 0_f64, 0_f64)
+    }
+
+    // rotation around the normalized vector `axis`
+    pub fn from_axis_angle(axis: &Vec3d, angle: f64) -> Self {
+        let (sin, cos) = (f64::sin(-angle / 2_f64), f64::cos(-angle / 2_f64));
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    // rotation by yaw around Y, then by pitch around X and by roll around Z
+    pub fn from_euler(yaw: f64, pitch: f64, roll: f64) -> Self {
+        Self::from_axis_angle(&Axis::Z.vector(), roll)
+            * Self::from_axis_angle(&Axis::X.vector(), pitch)
+            * Self::from_axis_angle(&Axis::Y.vector(), yaw)
+    }
+
+    // (yaw, pitch, roll) of `from_euler`. In the gimbal lock the roll is zero and the
+    // yaw takes the whole rotation around the view axis.
+    pub fn to_euler(self) -> (f64, f64, f64) {
+        let m = self.to_matrix();
+        let cos = f64::hypot(m.get(1, 0), m.get(1, 1));
+        let pitch = f64::atan2(-m.get(1, 2), cos);
+        if cos < constants::GIMBAL_EPS {
+            return (f64::atan2(-m.get(2, 0), m.get(0, 0)), pitch, 0_f64);
+        }
+        let yaw = f64::atan2(m.get(0, 2), m.get(2, 2));
+        let roll = f64::atan2(m.get(1, 0), m.get(1, 1));
+        (yaw, pitch, roll)
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn len(&self) -> f64 {
+        f64::sqrt(self.dot(self))
+    }
+
+    pub fn normalize(&mut self) {
+        let len = self.len();
+        self.w /= len;
+        self.x /= len;
+        self.y /= len;
+        self.z /= len;
+    }
+
+    // inverse rotation of the unit quaternion
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    // spherical interpolation by the shortest arc, `t` is in [0, 1]
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        let mut dot = self.dot(other);
+        let mut other = *other;
+        if dot < 0_f64 {
+            dot = -dot;
+            other = Self::new(-other.w, -other.x, -other.y, -other.z);
+        }
+        let (k1, k2) = if dot > 1_f64 - constants::SLERP_EPS {
+            (1_f64 - t, t)
+        } else {
+            let angle = f64::acos(dot);
+            let sin = f64::sin(angle);
+            (
+                f64::sin((1_f64 - t) * angle) / sin,
+                f64::sin(t * angle) / sin,
+            )
+        };
+        let mut res = Self::new(
+            k1 * self.w + k2 * other.w,
+            k1 * self.x + k2 * other.x,
+            k1 * self.y + k2 * other.y,
+            k1 * self.z + k2 * other.z,
+        );
+        res.normalize();
+        res
+    }
+
+    pub fn rotate_vec(&self, v: &Vec3d) -> Vec3d {
+        let u = Vec3d::new(self.x, self.y, self.z);
+        let uv = u.vector_mul(v);
+        let uuv = u.vector_mul(&uv);
+        Vec3d::new(
+            v.x + 2_f64 * (self.w * uv.x + uuv.x),
+            v.y + 2_f64 * (self.w * uv.y + uuv.y),
+            v.z + 2_f64 * (self.w * uv.z + uuv.z),
+        )
+    }
+
+    pub fn to_matrix(self) -> Matrix4 {
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        Matrix4::from([
+            [
+                1_f64 - 2_f64 * (y * y + z * z),
+                2_f64 * (x * y + w * z),
+                2_f64 * (x * z - w * y),
+                0_f64,
+            ],
+            [
+                2_f64 * (x * y - w * z),
+                1_f64 - 2_f64 * (x * x + z * z),
+                2_f64 * (y * z + w * x),
+                0_f64,
+            ],
+            [
+                2_f64 * (x * z + w * y),
+                2_f64 * (y * z - w * x),
+                1_f64 - 2_f64 * (x * x + y * y),
+                0_f64,
+            ],
+            [0_f64, 0_f64, 0_f64, 1_f64],
+        ])
+    }
+}
+
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct Matrix4 {
     data: [[f64; 4]; 4],
@@ -132,6 +303,151 @@ impl Matrix4 {
             data: [[0.0; 4]; 4],
         }
     }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row][col]
+    }
+
+    pub fn translation(v: &Vec3d) -> Self {
+        let mut res = Self::identity();
+        res.data[3] = [v.x, v.y, v.z, 1_f64];
+        res
+    }
+
+    pub fn scaling(val: f64) -> Self {
+        let mut res = Self::identity();
+        for i in 0..3 {
+            res.data[i][i] = val;
+        }
+        res
+    }
+
+    // scaling, then rotation and translation
+    pub fn from_trs(translation: &Vec3d, rotation: &Quaternion, scale: f64) -> Self {
+        Self::scaling(scale) * rotation.to_matrix() * Self::translation(translation)
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn transpose(&self) -> Self {
+        let mut res = Self::new();
+        for (i, row) in res.data.iter_mut().enumerate() {
+            for (j, elem) in row.iter_mut().enumerate() {
+                *elem = self.data[j][i];
+            }
+        }
+        res
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, `None` for the singular matrix
+    // (the matrix with NaN or infinity is singular too)
+    pub fn inverse(&self) -> Option<Self> {
+        if self.data.iter().flatten().any(|elem| !elem.is_finite()) {
+            return None;
+        }
+        let mut lhs = self.data;
+        let mut res = Self::identity().data;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| {
+                    lhs[i][col]
+                        .abs()
+                        .partial_cmp(&lhs[j][col].abs())
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap();
+            // elimination may overflow
+            let max = lhs[pivot][col].abs();
+            if !max.is_finite() || max < constants::SINGULAR_EPS {
+                return None;
+            }
+            lhs.swap(col, pivot);
+            res.swap(col, pivot);
+
+            let k = lhs[col][col];
+            for j in 0..4 {
+                lhs[col][j] /= k;
+                res[col][j] /= k;
+            }
+            for i in (0..4).filter(|&i| i != col) {
+                let k = lhs[i][col];
+                for j in 0..4 {
+                    lhs[i][j] -= k * lhs[col][j];
+                    res[i][j] -= k * res[col][j];
+                }
+            }
+        }
+        Some(Self::from(res))
+    }
+}
+
+// Right-handed view and projections of OpenGL (transposed for the row vectors): the
+// camera looks along -Z, the projected volume is the cube [-1, 1].
+#[cfg_attr(not(test), allow(dead_code))]
+impl Matrix4 {
+    pub fn look_at(eye: &Point3d, target: &Point3d, up: &Vec3d) -> Self {
+        let mut back = Vec3d::from_pts(target, eye);
+        back.normalize();
+        let mut right = up.vector_mul(&back);
+        right.normalize();
+        let up = back.vector_mul(&right);
+
+        let origin = Vec3d::new(eye.x, eye.y, eye.z);
+        Self::from([
+            [right.x, up.x, back.x, 0_f64],
+            [right.y, up.y, back.y, 0_f64],
+            [right.z, up.z, back.z, 0_f64],
+            [
+                -right.scalar_mul(&origin),
+                -up.scalar_mul(&origin),
+                -back.scalar_mul(&origin),
+                1_f64,
+            ],
+        ])
+    }
+
+    // `fov` is the vertical angle of the view
+    pub fn perspective(fov: f64, aspect: f64, near: f64, far: f64) -> Self {
+        let f = 1_f64 / f64::tan(fov / 2_f64);
+        Self::from([
+            [f / aspect, 0_f64, 0_f64, 0_f64],
+            [0_f64, f, 0_f64, 0_f64],
+            [0_f64, 0_f64, (far + near) / (near - far), -1_f64],
+            [0_f64, 0_f64, 2_f64 * far * near / (near - far), 0_f64],
+        ])
+    }
+
+    pub fn orthographic(left: f64, right: f64, bottom: f64, top: f64, near: f64, far: f64) -> Self {
+        Self::from([
+            [2_f64 / (right - left), 0_f64, 0_f64, 0_f64],
+            [0_f64, 2_f64 / (top - bottom), 0_f64, 0_f64],
+            [0_f64, 0_f64, -2_f64 / (far - near), 0_f64],
+            [
+                -(right + left) / (right - left),
+                -(top + bottom) / (top - bottom),
+                -(far + near) / (far - near),
+                1_f64,
+            ],
+        ])
+    }
+
+    // transformation with the perspective division, `None` for the point at infinity
+    pub fn project(&self, point: &Point3d) -> Option<Point3d> {
+        let coords = [point.x, point.y, point.z, 1_f64];
+        let mut res = [0_f64; 4];
+        for (i, elem) in res.iter_mut().enumerate() {
+            for (j, coord) in coords.iter().enumerate() {
+                *elem += coord * self.data[j][i];
+            }
+        }
+        if res[3].abs() < constants::SINGULAR_EPS {
+            return None;
+        }
+        Some(Point3d::new(
+            res[0] / res[3],
+            res[1] / res[3],
+            res[2] / res[3],
+        ))
+    }
 }
 
 impl From<[[f64; 4]; 4]> for Matrix4 {
@@ -156,18 +472,23 @@ impl MulAssign<Matrix4> for Matrix4 {
     }
 }
 
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(mut self, rhs: Matrix4) -> Matrix4 {
+        self *= rhs;
+        self
+    }
+}
+
 pub trait Transformator {
     fn mov(&mut self, val: f64, axis: Axis);
     fn rotate(&mut self, angle: f64, axis: Axis);
-    fn scale(&mut self, val: f64);
+    // rotation around the normalized vector `axis`, in the same direction as `rotate`
+    fn rotate_axis(&mut self, angle: f64, axis: &Vec3d);
     fn apply_to_point(&self, point: &mut Point3d);
 }
 
-pub trait CenterTransformator: Transformator {
-    fn rotate_center(&mut self, angle: f64, axis: Axis);
-    fn scale_center(&mut self, val: f64);
-}
-
 impl Transformator for Matrix4 {
     fn apply_to_point(&self, point: &mut Point3d) {
         let old_coords = [point.x, point.y, point.z, 1_f64];
@@ -192,67 +513,11 @@ impl Transformator for Matrix4 {
     }
 
     fn rotate(&mut self, angle: f64, axis: Axis) {
-        let rhs = match axis {
-            Axis::Y => Matrix4::from([
-                [f64::cos(angle), 0_f64, f64::sin(angle), 0_f64],
-                [0_f64, 1_f64, 0_f64, 0_f64],
-                [-f64::sin(angle), 0_f64, f64::cos(angle), 0_f64],
-                [0_f64, 0_f64, 0_f64, 1_f64],
-            ]),
-            Axis::X => Matrix4::from([
-                [1_f64, 0_f64, 0_f64, 0_f64],
-                [0_f64, f64::cos(angle), -f64::sin(angle), 0_f64],
-                [0_f64, f64::sin(angle), f64::cos(angle), 0_f64],
-                [0_f64, 0_f64, 0_f64, 1_f64],
-            ]),
-            Axis::Z => Matrix4::from([
-                [f64::cos(angle), -f64::sin(angle), 0_f64, 0_f64],
-                [f64::sin(angle), f64::cos(angle), 0_f64, 0_f64],
-                [0_f64, 0_f64, 1_f64, 0_f64],
-                [0_f64, 0_f64, 0_f64, 0_f64],
-            ]),
-        };
-
-        *self *= rhs;
+        self.rotate_axis(angle, &axis.vector());
     }
 
-    fn scale(&mut self, val: f64) {
-        let scale_matrix = Matrix4::from([
-            [val, 0_f64, 0_f64, 0_f64],
-            [0_f64, val, 0_f64, 0_f64],
-            [0_f64, 0_f64, val, 0_f64],
-            [0_f64, 0_f64, 0_f64, 1_f64],
-        ]);
-
-        *self *= scale_matrix;
-    }
-}
-
-impl CenterTransformator for Matrix4 {
-    fn rotate_center(&mut self, angle: f64, axis: Axis) {
-        let pos = [self.data[3][0], self.data[3][1], self.data[3][2]];
-        for elem in self.data[3][0..2].iter_mut() {
-            *elem = 0_f64;
-        }
-
-        self.rotate(angle, axis);
-
-        for (elem, copy) in self.data[3][0..2].iter_mut().zip(pos.iter()) {
-            *elem = *copy;
-        }
-    }
-
-    fn scale_center(&mut self, val: f64) {
-        let pos = [self.data[3][0], self.data[3][1], self.data[3][2]];
-        for elem in self.data[3][0..2].iter_mut() {
-            *elem = 0_f64;
-        }
-
-        self.scale(val);
-
-        for (elem, copy) in self.data[3][0..2].iter_mut().zip(pos.iter()) {
-            *elem = *copy;
-        }
+    fn rotate_axis(&mut self, angle: f64, axis: &Vec3d) {
+        *self *= Quaternion::from_axis_angle(axis, angle).to_matrix();
     }
 }
 
@@ -282,3 +547,297 @@ impl Section {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_eq(a: &Matrix4, b: &Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_relative_eq!(a.get(i, j), b.get(i, j), epsilon = 1e-9);
+            }
+        }
+    }
+
+    fn assert_point_eq(a: &Point3d, b: &Point3d) {
+        assert_relative_eq!(a.x, b.x, epsilon = 1e-9);
+        assert_relative_eq!(a.y, b.y, epsilon = 1e-9);
+        assert_relative_eq!(a.z, b.z, epsilon = 1e-9);
+    }
+
+    fn transformed(m: &Matrix4, x: f64, y: f64, z: f64) -> Point3d {
+        let mut p = Point3d::new(x, y, z);
+        m.apply_to_point(&mut p);
+        p
+    }
+
+    fn unit(x: f64, y: f64, z: f64) -> Vec3d {
+        let mut v = Vec3d::new(x, y, z);
+        v.normalize();
+        v
+    }
+
+    fn sample() -> Matrix4 {
+        Matrix4::from_trs(
+            &Vec3d::new(3.0, -2.0, 5.0),
+            &Quaternion::from_axis_angle(&unit(1.0, 2.0, -0.5), 0.8),
+            1.7,
+        )
+    }
+
+    #[test]
+    fn axis_rotations_match_explicit_matrices() {
+        let (sin, cos) = (f64::sin(0.3), f64::cos(0.3));
+        let expected = [
+            (
+                Axis::X,
+                [
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, cos, -sin, 0.0],
+                    [0.0, sin, cos, 0.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                ],
+            ),
+            (
+                Axis::Y,
+                [
+                    [cos, 0.0, sin, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [-sin, 0.0, cos, 0.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                ],
+            ),
+            (
+                Axis::Z,
+                [
+                    [cos, -sin, 0.0, 0.0],
+                    [sin, cos, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                ],
+            ),
+        ];
+        for &(axis, data) in expected.iter() {
+            let mut m = Matrix4::identity();
+            m.rotate(0.3, axis);
+            assert_matrix_eq(&m, &Matrix4::from(data));
+        }
+    }
+
+    #[test]
+    fn z_rotation_keeps_translation() {
+        let mut m = Matrix4::identity();
+        m.mov(10.0, Axis::X);
+        m.rotate(std::f64::consts::FRAC_PI_2, Axis::Z);
+        m *= Matrix4::translation(&Vec3d::new(0.0, 0.0, 7.0));
+        assert_relative_eq!(m.get(3, 3), 1.0);
+        assert_point_eq(
+            &transformed(&m, 0.0, 0.0, 0.0),
+            &Point3d::new(0.0, -10.0, 7.0),
+        );
+    }
+
+    #[test]
+    fn rotate_axis_matches_rotate_and_keeps_center() {
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let (mut a, mut b) = (sample(), sample());
+            a.rotate(0.7, axis);
+            b.rotate_axis(0.7, &axis.vector());
+            assert_matrix_eq(&a, &b);
+        }
+
+        // rotation around the center keeps all three of its coordinates
+        let center = Vec3d::new(3.0, -2.0, 5.0);
+        let mut m = Matrix4::translation(&Vec3d::new(-center.x, -center.y, -center.z));
+        m.rotate_axis(1.1, &unit(0.5, 1.0, -2.0));
+        m *= Matrix4::translation(&center);
+        assert_point_eq(
+            &transformed(&m, center.x, center.y, center.z),
+            &Point3d::new(center.x, center.y, center.z),
+        );
+    }
+
+    #[test]
+    fn composition_applies_left_first() {
+        let m = Matrix4::scaling(2.0)
+            * Quaternion::from_axis_angle(&Axis::Z.vector(), std::f64::consts::FRAC_PI_2)
+                .to_matrix()
+            * Matrix4::translation(&Vec3d::new(1.0, 2.0, 3.0));
+        // (1, 0, 0) -> (2, 0, 0) -> (0, -2, 0) -> (1, 0, 3)
+        assert_point_eq(
+            &transformed(&m, 1.0, 0.0, 0.0),
+            &Point3d::new(1.0, 0.0, 3.0),
+        );
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let m = sample();
+        let t = m.transpose();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(m.get(i, j), t.get(j, i));
+            }
+        }
+        assert_matrix_eq(&t.transpose(), &m);
+    }
+
+    #[test]
+    fn inverse_gives_identity() {
+        let m = sample();
+        let inverse = m.inverse().unwrap();
+        assert_matrix_eq(&(m.clone() * inverse.clone()), &Matrix4::identity());
+        assert_matrix_eq(&(inverse * m), &Matrix4::identity());
+    }
+
+    #[test]
+    fn inverse_of_rotation_is_conjugate() {
+        let q = Quaternion::from_axis_angle(&unit(-0.3, 0.4, 1.0), 2.1);
+        assert_matrix_eq(
+            &q.to_matrix().inverse().unwrap(),
+            &q.conjugate().to_matrix(),
+        );
+    }
+
+    #[test]
+    fn inverse_of_rotation_is_transpose() {
+        let r = Quaternion::from_axis_angle(&unit(-0.3, 0.4, 1.0), 2.1).to_matrix();
+        assert_matrix_eq(&r.inverse().unwrap(), &r.transpose());
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scaling(0.0).inverse().is_none());
+        assert!(Matrix4::new().inverse().is_none());
+
+        let mut m = sample();
+        m.mov(f64::NAN, Axis::Y);
+        assert!(m.inverse().is_none());
+        assert!(Matrix4::scaling(f64::INFINITY).inverse().is_none());
+    }
+
+    #[test]
+    fn look_at_puts_target_on_negative_z() {
+        let eye = Point3d::new(4.0, 3.0, 10.0);
+        let target = Point3d::new(1.0, -1.0, 2.0);
+        let m = Matrix4::look_at(&eye, &target, &Axis::Y.vector());
+        assert_point_eq(&transformed(&m, eye.x, eye.y, eye.z), &Point3d::default());
+
+        let dist = Vec3d::from_pts(&eye, &target).len();
+        let p = transformed(&m, target.x, target.y, target.z);
+        assert_point_eq(&p, &Point3d::new(0.0, 0.0, -dist));
+        // up stays up
+        let up = transformed(&m, target.x, target.y + 1.0, target.z);
+        assert!(up.y > p.y);
+        assert_relative_eq!(up.x, 0.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_planes() {
+        let m = Matrix4::perspective(std::f64::consts::FRAC_PI_2, 2.0, 1.0, 100.0);
+        let near = m.project(&Point3d::new(0.0, 0.0, -1.0)).unwrap();
+        let far = m.project(&Point3d::new(0.0, 0.0, -100.0)).unwrap();
+        assert_relative_eq!(near.z, -1.0, epsilon = 1e-9);
+        assert_relative_eq!(far.z, 1.0, epsilon = 1e-9);
+        // corner of the near plane: tan(fov / 2) = 1
+        let corner = m.project(&Point3d::new(2.0, 1.0, -1.0)).unwrap();
+        assert_point_eq(&corner, &Point3d::new(1.0, 1.0, -1.0));
+        // farther points are closer to the center
+        let distant = m.project(&Point3d::new(2.0, 1.0, -10.0)).unwrap();
+        assert_relative_eq!(distant.x, 0.1, epsilon = 1e-9);
+        assert!(m.project(&Point3d::new(1.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_cube() {
+        let m = Matrix4::orthographic(-4.0, 2.0, -1.0, 3.0, 0.5, 10.0);
+        let min = m.project(&Point3d::new(-4.0, -1.0, -0.5)).unwrap();
+        let max = m.project(&Point3d::new(2.0, 3.0, -10.0)).unwrap();
+        assert_point_eq(&min, &Point3d::new(-1.0, -1.0, -1.0));
+        assert_point_eq(&max, &Point3d::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn quaternion_matches_rotate_axis() {
+        let axis = unit(0.2, -1.0, 0.7);
+        let q = Quaternion::from_axis_angle(&axis, 1.3);
+        let mut m = Matrix4::identity();
+        m.rotate_axis(1.3, &axis);
+        assert_matrix_eq(&q.to_matrix(), &m);
+
+        let v = Vec3d::new(1.5, -0.5, 2.0);
+        let rotated = q.rotate_vec(&v);
+        assert_point_eq(
+            &Point3d::new(rotated.x, rotated.y, rotated.z),
+            &transformed(&m, v.x, v.y, v.z),
+        );
+    }
+
+    #[test]
+    fn quaternion_product_matches_matrix_product() {
+        let a = Quaternion::from_axis_angle(&unit(1.0, 1.0, 0.0), 0.4);
+        let b = Quaternion::from_axis_angle(&unit(0.0, -0.2, 1.0), 1.1);
+        assert_matrix_eq(&(b * a).to_matrix(), &(a.to_matrix() * b.to_matrix()));
+
+        let id = a * a.conjugate();
+        assert_relative_eq!(id.w, 1.0, epsilon = 1e-12);
+        assert_relative_eq!(Vec3d::new(id.x, id.y, id.z).len(), 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        for &(yaw, pitch, roll) in [(0.3, -0.7, 1.2), (-2.5, 1.0, -3.0), (1.0, 0.0, 0.0)].iter() {
+            let (y, p, r) = Quaternion::from_euler(yaw, pitch, roll).to_euler();
+            assert_relative_eq!(y, yaw, epsilon = 1e-9);
+            assert_relative_eq!(p, pitch, epsilon = 1e-9);
+            assert_relative_eq!(r, roll, epsilon = 1e-9);
+        }
+
+        let mut m = Matrix4::identity();
+        m.rotate(0.3, Axis::Y);
+        m.rotate(-0.7, Axis::X);
+        m.rotate(1.2, Axis::Z);
+        assert_matrix_eq(&Quaternion::from_euler(0.3, -0.7, 1.2).to_matrix(), &m);
+    }
+
+    #[test]
+    fn euler_angles_in_gimbal_lock() {
+        let half_pi = std::f64::consts::FRAC_PI_2;
+        let q = Quaternion::from_euler(0.4, half_pi, 0.9);
+        let (yaw, pitch, roll) = q.to_euler();
+        assert_relative_eq!(roll, 0.0);
+        assert_matrix_eq(
+            &Quaternion::from_euler(yaw, pitch, roll).to_matrix(),
+            &q.to_matrix(),
+        );
+    }
+
+    #[test]
+    fn slerp_interpolates_by_shortest_arc() {
+        let axis = Axis::Z.vector();
+        let a = Quaternion::from_axis_angle(&axis, 0.2);
+        let b = Quaternion::from_axis_angle(&axis, 1.8);
+        assert_matrix_eq(&a.slerp(&b, 0.0).to_matrix(), &a.to_matrix());
+        assert_matrix_eq(&a.slerp(&b, 1.0).to_matrix(), &b.to_matrix());
+        assert_matrix_eq(
+            &a.slerp(&b, 0.25).to_matrix(),
+            &Quaternion::from_axis_angle(&axis, 0.6).to_matrix(),
+        );
+
+        // the same rotation with the opposite sign
+        let neg = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+        assert_matrix_eq(
+            &a.slerp(&neg, 0.5).to_matrix(),
+            &Quaternion::from_axis_angle(&axis, 1.0).to_matrix(),
+        );
+        assert_relative_eq!(a.slerp(&b, 0.7).len(), 1.0, epsilon = 1e-12);
+        assert_matrix_eq(&a.slerp(&a, 0.5).to_matrix(), &a.to_matrix());
+        assert_matrix_eq(
+            &Quaternion::identity()
+                .slerp(&Quaternion::identity(), 0.3)
+                .to_matrix(),
+            &Matrix4::identity(),
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::sync::{Arc, Mutex};
+
+use super::prelude::*;
+
+// muscle placed on the carcass
+#[derive(Clone)]
+pub struct SceneMuscle {
+    name: String,
+    color: u32,
+    attachment: Attachment,
+    hill: HillParams,
+    excitation: Excitation,
+    muscle: Arc<Mutex<Muscle>>,
+}
+
+impl SceneMuscle {
+    pub fn new(
+        name: String,
+        color: u32,
+        attachment: Attachment,
+        hill: HillParams,
+        excitation: Excitation,
+        muscle: Muscle,
+    ) -> Self {
+        Self {
+            name,
+            color,
+            attachment,
+            hill,
+            excitation,
+            muscle: Arc::new(Mutex::new(muscle)),
+        }
+    }
+
+    // muscle is built with the profile from config (with length `len`) and then
+    // deformed to the length given by its attachment on the carcass
+    pub fn from_config(
+        mconf: MuscleConfig,
+        carcass: &Carcass,
+        index: usize,
+    ) -> Result<Self, ValidationError> {
+        let attachment = config_attachment(&mconf, carcass);
+        let color = mconf
+            .color
+            .unwrap_or(constants::MUSCLE_COLORS[index % constants::MUSCLE_COLORS.len()]);
+        let in_muscle = |error| ValidationError::Muscle {
+            name: mconf.name.clone(),
+            error: Box::new(error),
+        };
+        let keyframes = keyframe_model(&mconf, carcass, &attachment)
+            .map_err(|text| in_muscle(ValidationError::Keyframes(text)))?;
+        let mut muscle = Muscle::new(mconf.radiuses.clone(), mconf.grow_mults.clone(), mconf.len)
+            .map_err(in_muscle)?;
+        if let Some(tolerance) = mconf.tolerance {
+            muscle.set_tolerance(tolerance);
+        }
+        muscle.set_model(mconf.deformation);
+        muscle.set_keyframes(keyframes);
+        let hill = mconf
+            .hill
+            .unwrap_or_else(|| HillParams::from_muscle(&muscle));
+        muscle
+            .deform(carcass.muscle_len(&attachment) - mconf.len)
+            .map_err(|err| in_muscle(ValidationError::Placement(Box::new(err))))?;
+
+        Ok(Self::new(
+            mconf.name,
+            color,
+            attachment,
+            hill,
+            mconf.excitation,
+            muscle,
+        ))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn color(&self) -> u32 {
+        self.color
+    }
+
+    pub fn attachment(&self) -> &Attachment {
+        &self.attachment
+    }
+
+    pub fn hill(&self) -> &HillParams {
+        &self.hill
+    }
+
+    pub fn excitation(&self) -> &Excitation {
+        &self.excitation
+    }
+
+    pub fn muscle(&self) -> &Arc<Mutex<Muscle>> {
+        &self.muscle
+    }
+
+    // points and normals of the muscle in the carcass space, drawn flattened by contacts
+    // with other muscles
+    pub fn get_points_and_normals(
+        &self,
+        carcass: &Carcass,
+        contacts: &[Contact],
+    ) -> (Vec<Vec<Point3d>>, Vec<Vec<Point3d>>) {
+        let muscle = self.muscle.lock().unwrap();
+        self.get_points_and_normals_of(&muscle, carcass, contacts)
+    }
+
+    // the same for the other state of the muscle placed on the attachment of this one
+    pub fn get_points_and_normals_of(
+        &self,
+        muscle: &Muscle,
+        carcass: &Carcass,
+        contacts: &[Contact],
+    ) -> (Vec<Vec<Point3d>>, Vec<Vec<Point3d>>) {
+        let (mut points, mut normals) =
+            muscle.bget_points_and_normals(carcass.bounder(&self.attachment));
+        flatten_display_rings(&mut points, &mut normals, muscle.radiuses(), contacts);
+        let matrix = carcass.muscle_matrix(&self.attachment);
+
+        for (p_groups, n_groups) in points.iter_mut().zip(normals.iter_mut()) {
+            for (p, n) in p_groups.iter_mut().zip(n_groups.iter_mut()) {
+                matrix.apply_to_point(p);
+                matrix.apply_to_point(n);
+            }
+        }
+
+        (points, normals)
+    }
+
+    // Center of the node in the muscle space and its radius. The center is shifted
+    // like the surface pressed by the bones (`Muscle::apply_bounds`).
+    fn node_circle(&self, carcass: &Carcass, node: usize) -> Option<(Point3d, f64)> {
+        let muscle = self.muscle.lock().unwrap();
+        let radiuses = muscle.radiuses();
+        let rad = *radiuses.get(node)?;
+        let x = muscle.dx() * node as f64;
+        let mut center = Point3d::new(x, 0_f64, 0_f64);
+        if node > 0 && node + 1 < radiuses.len() {
+            center.y = f64::min(carcass.bounder(&self.attachment)(x) - rad, 0_f64);
+        }
+        Some((center, rad))
+    }
+
+    // center of the node in the carcass space
+    pub fn node_center(&self, carcass: &Carcass, node: usize) -> Option<Point3d> {
+        let (mut center, _) = self.node_circle(carcass, node)?;
+        carcass
+            .muscle_matrix(&self.attachment)
+            .apply_to_point(&mut center);
+        Some(center)
+    }
+
+    // outline of the node around the axis in the carcass space, empty for a bad node
+    pub fn node_ring(&self, carcass: &Carcass, node: usize) -> Vec<Point3d> {
+        let (center, rad) = match self.node_circle(carcass, node) {
+            Some(val) => val,
+            None => return Vec::new(),
+        };
+        let matrix = carcass.muscle_matrix(&self.attachment);
+        (0..=constants::RING_SEGMENTS)
+            .map(|i| {
+                let angle =
+                    2_f64 * std::f64::consts::PI * i as f64 / constants::RING_SEGMENTS as f64;
+                let mut p = Point3d::new(
+                    center.x,
+                    center.y + rad * f64::cos(angle),
+                    rad * f64::sin(angle),
+                );
+                matrix.apply_to_point(&mut p);
+                p
+            })
+            .collect()
+    }
+
+    // node, which is the nearest along the muscle to the point of the carcass space
+    pub fn nearest_node(&self, carcass: &Carcass, p: &Point3d) -> usize {
+        let mut p = *p;
+        if let Some(inverse) = carcass.muscle_matrix(&self.attachment).inverse() {
+            inverse.apply_to_point(&mut p);
+        }
+        let muscle = self.muscle.lock().unwrap();
+        let last = muscle.radiuses().len() - 1;
+        let node = f64::round(p.x / muscle.dx()).max(0_f64) as usize;
+        node.min(last)
+    }
+}
+
+// keyframes of the config with angles converted to lengths of the muscle
+pub fn keyframe_model(
+    mconf: &MuscleConfig,
+    carcass: &Carcass,
+    attachment: &Attachment,
+) -> Result<Option<KeyframeModel>, String> {
+    if mconf.keyframes.is_empty() {
+        return Ok(None);
+    }
+    let frames = mconf
+        .keyframes
+        .iter()
+        .map(|k| {
+            let angle = std::f64::consts::PI - k.angle.to_radians();
+            (carcass.muscle_len_at(attachment, angle), k.radiuses.clone())
+        })
+        .collect();
+    KeyframeModel::new(frames, mconf.radiuses.len()).map(Some)
+}
+
+// attachment of the config, the muscle of the carcass by default
+pub fn config_attachment(mconf: &MuscleConfig, carcass: &Carcass) -> Attachment {
+    match mconf.attachment {
+        Some([origin, insertion]) => Attachment::new(origin, insertion),
+        None => carcass.default_attachment(),
+    }
+}
+
+// carcass of the validated config in the initial angle, which is set by the first muscle
+// (it has its config length)
+pub fn build_carcass(config: &Config) -> Result<Carcass, ValidationError> {
+    let (mconf, cconf) = (&config.muscles[0], &config.carcass_config);
+    let mut carcass = Carcass::new(cconf.data, cconf.thickness, mconf.len);
+    if let Some([origin, insertion]) = mconf.attachment {
+        let angle = carcass.angle_for_len(&Attachment::new(origin, insertion), mconf.len);
+        carcass.set_angle(angle);
+    }
+    validate_joint(&carcass)?;
+    Ok(carcass)
+}
+
+// all muscles of the config are placed on the carcass
+pub fn build_scene(config: Config) -> Result<(Vec<SceneMuscle>, Carcass), ValidationError> {
+    validate_config(&config)?;
+    let carcass = build_carcass(&config)?;
+    let muscles = config
+        .muscles
+        .into_iter()
+        .enumerate()
+        .map(|(i, mconf)| SceneMuscle::from_config(mconf, &carcass, i))
+        .collect::<Result<_, _>>()?;
+
+    Ok((muscles, carcass))
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use super::prelude::*;
+
+// current state of the muscle, parameters are taken from the config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MuscleState {
+    pub radiuses: Vec<f64>,
+    pub grow_mults: Vec<f64>,
+    pub len: f64,
+    // lengths allowed by `MIN_PART` and `MAX_PART`
+    pub len_range: [f64; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedView {
+    pub name: String,
+    pub camera: Camera,
+}
+
+// Saved state of the application: the scene is built by the config (with colors and
+// deformation models of the muscles), then the muscles and the joint are set to their
+// saved state. Colors of the carcass and the shading are constants, so the light source
+// is the only saved setting of the rendering.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub config: Config,
+    // angle in the elbow (degrees, 180 is the extended arm)
+    pub angle: f64,
+    pub muscles: Vec<MuscleState>,
+    pub selected: usize,
+    pub activations: Vec<f64>,
+    pub force_model: ForceModel,
+    pub load: Option<Load>,
+    pub camera: Camera,
+    pub views: Vec<SavedView>,
+    pub pivot: Pivot,
+    pub light_source: Point3d,
+}
+
+impl Session {
+    // session of the scene with the default view
+    pub fn new(mut config: Config, muscles: &[SceneMuscle], carcass: &Carcass) -> Self {
+        let states = muscles
+            .iter()
+            .zip(config.muscles.iter_mut())
+            .map(|(m, mconf)| {
+                let muscle = m.muscle().lock().unwrap();
+                mconf.color = Some(m.color());
+                mconf.deformation = muscle.model();
+                let (min, max) = muscle.len_range();
+                MuscleState {
+                    radiuses: muscle.radiuses().to_vec(),
+                    grow_mults: muscle.grow_mults().to_vec(),
+                    len: muscle.len(),
+                    len_range: [min, max],
+                }
+            })
+            .collect();
+        Self {
+            config,
+            angle: carcass.angle().to_degrees(),
+            muscles: states,
+            selected: 0,
+            activations: vec![0_f64; muscles.len()],
+            force_model: ForceModel::Hill,
+            load: None,
+            camera: Camera::default(),
+            views: Vec::new(),
+            pivot: Pivot::SceneCenter,
+            light_source: constants::LIGHT_SOURCE,
+        }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let reader = File::open(path).map_err(|err| format!("Can't open session!\n{}", err))?;
+        let session: Session = serde_yaml::from_reader(BufReader::new(reader))
+            .map_err(|err| format!("Bad session!\n{}", err))?;
+        session
+            .validate()
+            .map_err(|err| format!("Bad session!\n{}", err))?;
+        Ok(session)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let yaml = serde_yaml::to_string(self).map_err(|err| err.to_string())?;
+        fs::write(path, yaml).map_err(|err| format!("Can't write session!\n{}", err))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_config(&self.config)?;
+        let n = self.config.muscles.len();
+        if self.muscles.len() != n || self.activations.len() != n {
+            return Err(format!(
+                "Numbers of muscles differ!\nconfig: {};\nstates: {};\nactivations: {}.",
+                n,
+                self.muscles.len(),
+                self.activations.len()
+            ));
+        }
+        if self.selected >= n {
+            return Err(format!("Bad selected muscle: {}", self.selected));
+        }
+        // NaN isn't in the range too
+        if let Some(&value) = self
+            .activations
+            .iter()
+            .find(|a| !(0_f64..=1_f64).contains(*a))
+        {
+            return Err(ValidationError::BadParameter {
+                name: "activation",
+                value,
+            }
+            .into());
+        }
+        if let Some(load) = self.load {
+            if load.muscle >= n {
+                return Err(format!("Bad muscle of the load: {}", load.muscle));
+            }
+            validate_positive("load mass", load.mass, true)?;
+        }
+        if let Pivot::Node(muscle, _) = self.pivot {
+            if muscle >= n {
+                return Err(format!("Bad muscle of the pivot: {}", muscle));
+            }
+        }
+
+        // angle must be in the joint limit, muscles must fit their attachments in it
+        if !(0_f64..=180_f64).contains(&self.angle) {
+            return Err(ValidationError::BadParameter {
+                name: "angle",
+                value: self.angle,
+            }
+            .into());
+        }
+        let mut carcass = build_carcass(&self.config)?;
+        carcass.set_angle(self.angle.to_radians());
+        validate_joint(&carcass)?;
+        for (state, mconf) in self.muscles.iter().zip(self.config.muscles.iter()) {
+            let [min, max] = state.len_range;
+            let expected = carcass.muscle_len(&config_attachment(mconf, &carcass));
+            let error = if !(min..=max).contains(&state.len) {
+                ValidationError::MuscleLength {
+                    len: state.len,
+                    min,
+                    max,
+                }
+            } else if (state.len - expected).abs() > constants::STATE_LEN_TOLERANCE * expected {
+                ValidationError::Detached {
+                    len: state.len,
+                    expected,
+                }
+            } else {
+                continue;
+            };
+            return Err(ValidationError::Muscle {
+                name: mconf.name.clone(),
+                error: Box::new(error),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    // muscles built by the config get their saved profiles
+    pub fn build_scene(&self) -> Result<(Vec<SceneMuscle>, Carcass), String> {
+        let (muscles, mut carcass) = build_scene(self.config.clone())?;
+        carcass.set_angle(self.angle.to_radians());
+        for ((m, state), mconf) in muscles
+            .iter()
+            .zip(self.muscles.iter())
+            .zip(self.config.muscles.iter())
+        {
+            m.muscle()
+                .lock()
+                .unwrap()
+                .set_state(
+                    state.radiuses.clone(),
+                    state.grow_mults.clone(),
+                    state.len,
+                    (state.len_range[0], state.len_range[1]),
+                )
+                .map_err(|err| ValidationError::Muscle {
+                    name: mconf.name.clone(),
+                    error: Box::new(err),
+                })?;
+        }
+        Ok((muscles, carcass))
+    }
+}
+
+// session of the config in its initial state, which can be opened by `--session`
+pub fn session_command(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: session CONFIG OUTPUT";
+    if args.len() != 2 {
+        return Err(usage.to_string());
+    }
+    let config = read_config(&args[0])?;
+    let (muscles, carcass) = build_scene(config.clone())?;
+    Session::new(config, &muscles, &carcass).write(&args[1])
+}
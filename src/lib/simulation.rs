@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+// excitation signal of the muscle in [0, 1] as a function of time (seconds)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Excitation {
+    Constant(f64),
+    Step { time: f64, value: f64 },
+    Sine { period: f64, min: f64, max: f64 },
+    // [time, value] pairs with linear interpolation between them
+    Keyframes(Vec<[f64; 2]>),
+}
+
+impl Default for Excitation {
+    fn default() -> Self {
+        Excitation::Constant(0_f64)
+    }
+}
+
+impl Excitation {
+    pub fn value(&self, time: f64) -> f64 {
+        let value = match self {
+            Excitation::Constant(value) => *value,
+            Excitation::Step { time: at, value } => {
+                if time >= *at {
+                    *value
+                } else {
+                    0_f64
+                }
+            }
+            Excitation::Sine { period, min, max } => {
+                let phase = 2_f64 * std::f64::consts::PI * time / period;
+                min + (max - min) * (1_f64 - f64::cos(phase)) / 2_f64
+            }
+            Excitation::Keyframes(frames) => match frames.iter().position(|f| f[0] > time) {
+                None => frames.last().map_or(0_f64, |f| f[1]),
+                Some(0) => frames[0][1],
+                Some(i) => {
+                    let (f1, f2) = (frames[i - 1], frames[i]);
+                    f1[1] + (f2[1] - f1[1]) * (time - f1[0]) / (f2[0] - f1[0])
+                }
+            },
+        };
+        value.clamp(0_f64, 1_f64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SimulationConfig {
+    // mass of the second bone (kg), it is considered as a uniform rod
+    pub forearm_mass: f64,
+    // viscous friction in the joint (N * m * s / rad)
+    pub damping: f64,
+    // integration step (seconds)
+    pub dt: f64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            forearm_mass: constants::FOREARM_MASS,
+            damping: constants::JOINT_DAMPING,
+            dt: constants::SIM_DT,
+        }
+    }
+}
+
+// state of the muscle-tendon unit
+#[derive(Clone, Copy, Debug)]
+pub struct HillState {
+    pub activation: f64,
+    pub fiber_len: f64,
+    pub tendon_force: f64,
+}
+
+// Forward dynamics of the joint driven by Hill-type muscles, integrated by the explicit
+// Euler method with the fixed time step.
+#[derive(Clone)]
+pub struct Simulation {
+    config: SimulationConfig,
+    time: f64,
+    omega: f64,
+    states: Vec<HillState>,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig, muscles: &[SceneMuscle], carcass: &Carcass) -> Self {
+        let states = muscles
+            .iter()
+            .map(|m| {
+                let mtu_len = carcass.muscle_len(m.attachment());
+                let fiber_len = m.hill().equilibrium_fiber_len(0_f64, mtu_len);
+                HillState {
+                    activation: 0_f64,
+                    fiber_len,
+                    tendon_force: m.hill().tendon_force(mtu_len - fiber_len),
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            time: 0_f64,
+            omega: 0_f64,
+            states,
+        }
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn states(&self) -> &[HillState] {
+        &self.states
+    }
+
+    // joint reached its limit
+    pub fn stop_joint(&mut self) {
+        self.omega = 0_f64;
+    }
+
+    // Makes one step and returns the new angle in the elbow. Muscles and carcass are
+    // not changed: the caller poses them with the returned angle.
+    pub fn step(&mut self, muscles: &[SceneMuscle], carcass: &Carcass) -> f64 {
+        let dt = self.config.dt;
+        let mut torque = -self.config.damping * self.omega;
+
+        for (m, state) in muscles.iter().zip(self.states.iter_mut()) {
+            let hill = m.hill();
+            let u = m.excitation().value(self.time);
+            state.activation += dt * hill.activation_derivative(u, state.activation);
+
+            let mtu_len = carcass.muscle_len(m.attachment());
+            state.tendon_force = hill.tendon_force(mtu_len - state.fiber_len);
+            let velocity =
+                hill.fiber_velocity(state.activation, state.fiber_len, state.tendon_force);
+            state.fiber_len = (state.fiber_len + velocity * dt)
+                .max(hill.optimal_fiber_len * constants::HILL_MIN_FIBER_PART)
+                .min(mtu_len);
+
+            // muscle pulls the joint to the side, where it becomes shorter
+            torque -= state.tendon_force * carcass.moment_arm(m.attachment())
+                / constants::UNITS_PER_METER;
+        }
+
+        let forearm_len = carcass.forearm_len() / constants::UNITS_PER_METER;
+        let inertia = self.config.forearm_mass * forearm_len * forearm_len / 3_f64;
+        self.omega += dt * torque / inertia;
+        self.time += dt;
+
+        carcass.angle() + self.omega * dt
+    }
+}
@@ -1,11 +1,19 @@
 use gdk::prelude::*;
 use gdk_pixbuf::{Colorspace, Pixbuf};
+use gio::prelude::*;
 use gtk::prelude::*;
+use log::{debug, info};
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use super::prelude::*;
 mod control_panel;
-use control_panel::setup_control_panel;
+use control_panel::{
+    choose_file, refresh_control_panel, setup_control_panel, show_error, show_pick,
+};
 
 macro_rules! clone {
     (@param _) => ( _ );
@@ -24,56 +32,145 @@ macro_rules! clone {
     );
 }
 
-pub fn process_key(controller: &Arc<Mutex<Controller>>, drawing_area: &gtk::DrawingArea, key: u16) {
+pub fn process_command(
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+    builder: &gtk::Builder,
+    command: Command,
+) {
     {
         let mut contr = controller.lock().unwrap();
-        contr.process_key(key);
+        contr.execute(command);
+    }
+    // restored state has its own selected muscle, pivot and load
+    if let Command::Undo | Command::Redo = command {
+        refresh_control_panel(builder, controller);
     }
 
     drawing_area.queue_draw();
 }
 
-pub fn build_ui(app: &gtk::Application) {
+// options of the GTK application given on the command line
+#[derive(Clone, Default)]
+pub struct UiOptions {
+    // `RELATIVE_CONF_PATH` of the current directory by default
+    pub config: Option<PathBuf>,
+    // glade file of the control panel instead of the embedded one
+    pub ui: Option<PathBuf>,
+    // scene is built again, when the config changes
+    pub watch: bool,
+    // session opened instead of the config
+    pub session: Option<PathBuf>,
+}
+
+impl UiOptions {
+    fn config_path(&self) -> Result<PathBuf, String> {
+        match &self.config {
+            Some(path) => Ok(path.clone()),
+            None => default_config_path(),
+        }
+    }
+}
+
+// Takes the options of the application, the rest of the arguments is passed to GTK.
+pub fn parse_ui_args(args: &[String]) -> Result<(UiOptions, Vec<String>), String> {
+    let usage = "Usage: [--config PATH] [--ui PATH] [--watch] [--session PATH]";
+    let mut options = UiOptions::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => options.config = Some(args.next().ok_or(usage)?.into()),
+            "--ui" => options.ui = Some(args.next().ok_or(usage)?.into()),
+            "--watch" => options.watch = true,
+            "--session" => options.session = Some(args.next().ok_or(usage)?.into()),
+            _ => rest.push(arg.clone()),
+        }
+    }
+    // config of the session is saved in it
+    if options.session.is_some() && options.watch {
+        return Err("Session can't be watched!".to_string());
+    }
+    Ok((options, rest))
+}
+
+// controller of the session or of the config
+fn new_controller(pb: Pixbuf, options: &UiOptions) -> Result<Controller, String> {
+    if let Some(path) = &options.session {
+        return Controller::from_session(pb, &Session::read(path)?);
+    }
+    let config = read_config(options.config_path()?)?;
+    let (muscles, carcass) = build_scene(config.clone())?;
+    Ok(Controller::new(
+        pb,
+        muscles,
+        Arc::new(Mutex::new(carcass)),
+        config,
+    ))
+}
+
+pub fn build_ui(app: &gtk::Application, options: &UiOptions) {
+    let pixbuf = Pixbuf::new(
+        Colorspace::Rgb,
+        constants::HAS_ALPHA,
+        constants::BITS_PER_COLOR,
+        constants::WIDTH as i32,
+        constants::HEIGHT as i32,
+    )
+    .unwrap();
+    // application quits without windows
+    let mut controller = match new_controller(pixbuf.clone(), options) {
+        Ok(val) => val,
+        Err(text) => {
+            show_error(text);
+            return;
+        }
+    };
+
+    let builder = match &options.ui {
+        Some(path) => {
+            let builder = gtk::Builder::new();
+            if let Err(err) = builder.add_from_file(path) {
+                show_error(format!("Can't load UI!\n{}", err));
+                return;
+            }
+            builder
+        }
+        None => gtk::Builder::from_string(constants::CONTROL_WINDOW_UI),
+    };
+
     let window = gtk::ApplicationWindow::new(app);
     window.set_title("Muscle");
 
-    let glade_src = "config/control_window.glade";
-    let builder = gtk::Builder::from_file(glade_src);
     let control_window: gtk::Window = builder.get_object("window").expect("Couldn't get window");
     control_window.set_application(Some(app));
     control_window.set_title("Control panel");
 
+    let keybindings = options
+        .config_path()
+        .and_then(|path| read_keybindings(&path));
+    let keybindings = Rc::new(keybindings.unwrap_or_else(|text| {
+        show_error(text);
+        Keybindings::default()
+    }));
+
     let fixed = gtk::Fixed::new();
     let drawing_area = gtk::DrawingArea::new();
     fixed.add(&drawing_area);
+    // help is drawn over the model
+    let help = gtk::Label::new(None);
+    help.set_markup(&format!(
+        "<span font_family=\"monospace\" background=\"black\" bgalpha=\"75%\" foreground=\"white\">{}</span>",
+        glib::markup_escape_text(&keybindings.help())
+    ));
+    help.set_no_show_all(true);
+    fixed.put(&help, constants::HELP_OFFSET, constants::HELP_OFFSET);
     window.add(&fixed);
     drawing_area.set_size_request(constants::WIDTH as i32, constants::HEIGHT as i32);
 
-    let Config {
-        muscle_config: mconf,
-        carcass_config: cconf,
-    } = read_from_config();
-    let muscle = Arc::new(Mutex::new(Muscle::new(
-        mconf.radiuses,
-        mconf.grow_mults,
-        mconf.len,
-    )));
-    let carcass = Arc::new(Mutex::new(Carcass::new(
-        cconf.data,
-        cconf.thickness,
-        mconf.len,
-    )));
-    let pixbuf = Pixbuf::new(
-        Colorspace::Rgb,
-        constants::HAS_ALPHA,
-        constants::BITS_PER_COLOR,
-        constants::WIDTH as i32,
-        constants::HEIGHT as i32,
-    )
-    .unwrap();
-
-    let mut controller = Controller::new(pixbuf.clone(), muscle, carcass);
     controller.update_pixbuf();
+    // controller is used only by the handlers of the GTK thread
+    #[allow(clippy::arc_with_non_send_sync)]
     let controller = Arc::new(Mutex::new(controller));
 
     drawing_area.connect_draw(clone!(pixbuf => move |_, context| {
@@ -82,12 +179,241 @@ pub fn build_ui(app: &gtk::Application) {
         Inhibit(false)
     }));
 
-    window.connect_key_press_event(clone!(controller, drawing_area => move |_, key| {
-        process_key(&controller, &drawing_area, key.get_hardware_keycode());
-        Inhibit(false)
-    }));
+    window.connect_key_press_event(
+        clone!(controller, drawing_area, builder, keybindings => move |_, key| {
+            let shortcut = Shortcut::from_event(*key.get_keyval(), key.get_state());
+            match keybindings.command(shortcut) {
+                Some(Command::Help) => help.set_visible(!help.get_visible()),
+                Some(command) => process_command(&controller, &drawing_area, &builder, command),
+                None => debug!("Unbound key: {}", shortcut.name()),
+            }
+            Inhibit(false)
+        }),
+    );
+
+    // opened session replaces the watched config
+    let watching = Rc::new(Cell::new(options.watch));
+    setup_mouse(&controller, &drawing_area, &builder);
+    setup_menu(
+        app,
+        &controller,
+        &drawing_area,
+        &builder,
+        &pixbuf,
+        &watching,
+    );
+    if options.watch {
+        match options.config_path() {
+            Ok(path) => setup_watch(path, &controller, &drawing_area, &builder, &watching),
+            Err(text) => show_error(text),
+        }
+    }
+
+    glib::timeout_add_local(
+        constants::SIM_FRAME_MS,
+        clone!(controller, drawing_area => move || {
+            let mut controller = controller.lock().unwrap();
+            let simulated = controller.simulation_frame();
+            if controller.camera_frame() || simulated {
+                drawing_area.queue_draw();
+            }
+            glib::Continue(true)
+        }),
+    );
 
     window.show_all();
-    setup_control_panel(&builder, &controller, &drawing_area);
+    setup_control_panel(&builder, &controller, &drawing_area, &keybindings);
     control_window.show_all();
 }
+
+// Config is read again, when the time of its modification changes. Time is lost, while
+// an editor replaces the file, so the missing file is skipped. Watching stops, when
+// a session is opened.
+fn setup_watch(
+    path: PathBuf,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+    builder: &gtk::Builder,
+    watching: &Rc<Cell<bool>>,
+) {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let last = Cell::new(modified(&path));
+    glib::timeout_add_local(
+        constants::WATCH_MS,
+        clone!(controller, drawing_area, builder, watching => move || {
+            if !watching.get() {
+                info!("STOPPED WATCHING: {}", path.display());
+                return glib::Continue(false);
+            }
+            let time = modified(&path);
+            if time.is_none() || time == last.get() {
+                return glib::Continue(true);
+            }
+            last.set(time);
+            let result = read_config(&path)
+                .and_then(|config| controller.lock().unwrap().reload_config(config));
+            match result {
+                Ok(()) => {
+                    info!("RELOADED: {}", path.display());
+                    refresh_control_panel(&builder, &controller);
+                    drawing_area.queue_draw();
+                }
+                Err(text) => show_error(text),
+            }
+            glib::Continue(true)
+        }),
+    );
+}
+
+// File menu of the main window, sessions are chosen by dialogs
+fn setup_menu(
+    app: &gtk::Application,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+    builder: &gtk::Builder,
+    pixbuf: &Pixbuf,
+    watching: &Rc<Cell<bool>>,
+) {
+    let file = gio::Menu::new();
+    file.append(Some("_Open session..."), Some("app.open_session"));
+    file.append(Some("_Save session..."), Some("app.save_session"));
+    let menubar = gio::Menu::new();
+    menubar.append_submenu(Some("_File"), &file);
+    app.set_menubar(Some(&menubar));
+
+    let open = gio::SimpleAction::new("open_session", None);
+    open.connect_activate(
+        clone!(controller, drawing_area, builder, pixbuf, watching => move |_, _| {
+            let path = match choose_file("Open session", gtk::FileChooserAction::Open) {
+                Some(path) => path,
+                None => return,
+            };
+            let result = Session::read(&path)
+                .and_then(|session| Controller::from_session(pixbuf.clone(), &session));
+            match result {
+                Ok(mut new) => {
+                    new.update_pixbuf();
+                    *controller.lock().unwrap() = new;
+                    watching.set(false);
+                }
+                Err(text) => {
+                    show_error(text);
+                    return;
+                }
+            }
+            refresh_control_panel(&builder, &controller);
+            drawing_area.queue_draw();
+        }),
+    );
+    app.add_action(&open);
+    app.set_accels_for_action("app.open_session", &["<Primary>o"]);
+
+    let save = gio::SimpleAction::new("save_session", None);
+    save.connect_activate(clone!(controller => move |_, _| {
+        let path = match choose_file("Save session", gtk::FileChooserAction::Save) {
+            Some(path) => path,
+            None => return,
+        };
+        let session = controller.lock().unwrap().session();
+        if let Err(text) = session.write(&path) {
+            show_error(text);
+        }
+    }));
+    app.add_action(&save);
+    app.set_accels_for_action("app.save_session", &["<Primary>s"]);
+}
+
+// left drag rotates the model, middle drag moves it, wheel scales it at the cursor,
+// left click picks the object, left drag of the ring of the picked node changes its
+// radius (grow mult with shift)
+fn setup_mouse(
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+    builder: &gtk::Builder,
+) {
+    drawing_area.add_events(
+        gdk::EventMask::BUTTON_PRESS_MASK
+            | gdk::EventMask::BUTTON_RELEASE_MASK
+            | gdk::EventMask::BUTTON_MOTION_MASK
+            | gdk::EventMask::SCROLL_MASK
+            | gdk::EventMask::SMOOTH_SCROLL_MASK,
+    );
+    // pressed button and the last position of the cursor
+    let drag = Rc::new(Cell::new(None::<(u32, (f64, f64))>));
+    // position of the press
+    let press = Rc::new(Cell::new((0_f64, 0_f64)));
+
+    drawing_area.connect_button_press_event(clone!(controller, drag, press => move |_, event| {
+        let (x, y) = event.get_position();
+        drag.set(Some((event.get_button(), (x, y))));
+        press.set((x, y));
+        if event.get_button() == constants::ROTATE_BUTTON {
+            let shift = event.get_state().contains(gdk::ModifierType::SHIFT_MASK);
+            controller.lock().unwrap().grab_ring(x, y, shift);
+        }
+        Inhibit(false)
+    }));
+    drawing_area.connect_button_release_event(
+        clone!(controller, drag, press, builder => move |area, event| {
+            drag.set(None);
+            let dragged = {
+                let mut controller = controller.lock().unwrap();
+                controller.end_drag();
+                controller.release_ring()
+            };
+            if let Some(pick) = dragged {
+                show_pick(&builder, &controller, pick);
+                return Inhibit(false);
+            }
+            let (x, y) = event.get_position();
+            let (px, py) = press.get();
+            if event.get_button() != constants::ROTATE_BUTTON
+                || f64::hypot(x - px, y - py) > constants::PICK_TOLERANCE
+            {
+                return Inhibit(false);
+            }
+            let pick = controller.lock().unwrap().pick(x, y);
+            // fields are filled after the controller is unlocked: their handlers lock it
+            if let Some(pick) = pick {
+                show_pick(&builder, &controller, pick);
+            }
+            area.queue_draw();
+            Inhibit(false)
+        }),
+    );
+    drawing_area.connect_motion_notify_event(clone!(controller, drag => move |area, event| {
+        let (button, from) = match drag.get() {
+            Some(val) => val,
+            None => return Inhibit(false),
+        };
+        let to = event.get_position();
+        match button {
+            constants::ROTATE_BUTTON => {
+                let mut controller = controller.lock().unwrap();
+                if !controller.drag_ring(to.0, to.1) {
+                    controller.arcball(from, to);
+                }
+            }
+            constants::PAN_BUTTON => controller
+                .lock()
+                .unwrap()
+                .pan(to.0 - from.0, to.1 - from.1),
+            _ => return Inhibit(false),
+        }
+        drag.set(Some((button, to)));
+        area.queue_draw();
+        Inhibit(false)
+    }));
+    drawing_area.connect_scroll_event(clone!(controller => move |area, event| {
+        let steps = match event.get_direction() {
+            gdk::ScrollDirection::Up => 1_f64,
+            gdk::ScrollDirection::Down => -1_f64,
+            gdk::ScrollDirection::Smooth => -event.get_delta().1,
+            _ => return Inhibit(false),
+        };
+        let (x, y) = event.get_position();
+        controller.lock().unwrap().zoom_at(x, y, steps);
+        area.queue_draw();
+        Inhibit(false)
+    }));
+}
@@ -21,16 +21,20 @@ pub fn setup_control_panel(
     builder: &gtk::Builder,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
+    keybindings: &Keybindings,
 ) {
-    for (btn_name, key) in constants::COMMANDS_BUTTONS
+    for (btn_name, &command) in constants::COMMANDS_BUTTONS
         .iter()
-        .zip(constants::CMDS_BTNS_KEY_MAP.iter())
+        .zip(constants::CMDS_BTNS_MAP.iter())
     {
         let btn: gtk::Button = builder
             .get_object(btn_name)
-            .expect(&format!("get {} object", btn_name));
-        btn.connect_clicked(clone!(controller, drawing_area, key => move |_| {
-            process_key(&controller, &drawing_area, key);
+            .unwrap_or_else(|| panic!("get {} object", btn_name));
+        if let Some(shortcut) = keybindings.shortcuts(command).first() {
+            btn.set_tooltip_text(Some(&shortcut.name()));
+        }
+        btn.connect_clicked(clone!(controller, drawing_area, builder => move |_| {
+            process_command(&controller, &drawing_area, &builder, command);
         }));
     }
     let mut inputs: Vec<gtk::Entry> = Vec::with_capacity(constants::INPUTS_AMOUNT);
@@ -42,16 +46,658 @@ pub fn setup_control_panel(
         rbtns.push(builder.get_object(rbtn_name).unwrap());
     }
 
-    setup_add(&rbtns, &inputs, &controller, &drawing_area);
-    setup_mod(&rbtns, &inputs, &controller, &drawing_area);
-    setup_del(&rbtns, &inputs, &controller, &drawing_area);
-    setup_rpm(&rbtns, &inputs, &controller, &drawing_area);
+    setup_add(&rbtns, &inputs, controller, drawing_area);
+    setup_mod(&rbtns, &inputs, controller, drawing_area);
+    setup_del(&rbtns, &inputs, controller, drawing_area);
+    setup_rpm(&rbtns, &inputs, controller, drawing_area);
     setup_next_prev(&rbtns, &inputs);
-    setup_light_button(&rbtns, &inputs, &controller, &drawing_area);
+    setup_light_button(&rbtns, &inputs, controller, drawing_area);
+    setup_muscle_select(builder, controller);
+    setup_mechanics(builder, controller, drawing_area);
+    setup_load(builder, controller, drawing_area);
+    setup_resample(builder, controller, drawing_area);
+    setup_smooth(builder, controller, drawing_area);
+    setup_metrics(builder, controller, drawing_area);
+    setup_explore(builder, controller);
+    setup_views(builder, controller, drawing_area);
+    setup_pivot(builder, controller);
+    setup_history(builder, controller, drawing_area);
+    // controller can be made by the session
+    refresh_control_panel(builder, controller);
+}
+
+// List is rebuilt, when the history changes; states after the current one are dimmed.
+// The clicked state is restored.
+fn setup_history(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let list: gtk::ListBox = builder
+        .get_object(constants::HISTORY_LIST)
+        .expect("get history_list object");
+    // rows are selected by the program too, only activation changes the state
+    list.connect_row_activated(clone!(controller, drawing_area, builder => move |_, row| {
+        controller.lock().unwrap().jump_to(row.get_index() as usize);
+        refresh_control_panel(&builder, &controller);
+        drawing_area.queue_draw();
+    }));
+
+    let revision = Rc::new(Cell::new(None));
+    drawing_area.connect_draw(clone!(controller => move |_, _| {
+        let ((names, current), rev) = {
+            let controller = controller.lock().unwrap();
+            (controller.history(), controller.history_revision())
+        };
+        if revision.get() == Some(rev) {
+            return Inhibit(false);
+        }
+        revision.set(Some(rev));
+        for child in list.get_children() {
+            list.remove(&child);
+        }
+        for (i, name) in names.iter().enumerate() {
+            let label = gtk::Label::new(Some(&format!("{}. {}", i, name)));
+            label.set_xalign(0_f32);
+            label.set_sensitive(i <= current);
+            list.add(&label);
+        }
+        list.show_all();
+        list.select_row(list.get_row_at_index(current as i32).as_ref());
+        Inhibit(false)
+    }));
+}
+
+// Fills the fields of the picked object: the muscle of the node is chosen and the node
+// is put in the node inputs, the light source in the light inputs.
+pub fn show_pick(builder: &gtk::Builder, controller: &Arc<Mutex<Controller>>, pick: Pick) {
+    let set_input = |index: usize, text: String| {
+        let input: gtk::Entry = builder
+            .get_object(constants::INPUTS_NAMES[index])
+            .unwrap_or_else(|| panic!("get {} object", constants::INPUTS_NAMES[index]));
+        input.get_buffer().set_text(&text);
+    };
+    match pick {
+        Pick::Node(muscle, node) => {
+            let name = controller.lock().unwrap().muscle_names()[muscle].clone();
+            // handler of the select makes the muscle selected
+            let select: gtk::ComboBoxText = builder
+                .get_object(constants::MUSCLE_SELECT)
+                .expect("get muscle_select object");
+            select.set_active_id(Some(&name));
+            set_input(constants::POS_INPUT, node.to_string());
+            let values = controller.lock().unwrap().get_node(node);
+            if let Ok((rad, gm)) = values {
+                set_input(constants::RAD_INPUT, rad.to_string());
+                set_input(constants::GM_INPUT, gm.to_string());
+            }
+        }
+        Pick::Light => {
+            let p = controller.lock().unwrap().light_source();
+            set_input(constants::X_INPUT, p.x.to_string());
+            set_input(constants::Y_INPUT, p.y.to_string());
+            set_input(constants::Z_INPUT, p.z.to_string());
+        }
+        // bones have no fields, they are only highlighted
+        Pick::Bone(_) => {}
+    }
+}
+
+// Selects are filled by the state of the new controller (of the opened session).
+pub fn refresh_control_panel(builder: &gtk::Builder, controller: &Arc<Mutex<Controller>>) {
+    let (names, selected, views, force_model, pivot, load) = {
+        let controller = controller.lock().unwrap();
+        (
+            controller.muscle_names(),
+            controller.selected(),
+            controller.view_names(),
+            controller.force_model(),
+            controller.pivot(),
+            load_text(&controller),
+        )
+    };
+    let select: gtk::ComboBoxText = builder
+        .get_object(constants::MUSCLE_SELECT)
+        .expect("get muscle_select object");
+    select.remove_all();
+    for name in names.iter() {
+        select.append(Some(name), name);
+    }
+    // handler of the select shows the model of the muscle
+    select.set_active(Some(selected as u32));
+
+    let view_select: gtk::ComboBoxText = builder
+        .get_object(constants::VIEW_SELECT)
+        .expect("get view_select object");
+    view_select.remove_all();
+    for preset in ViewPreset::ALL.iter() {
+        view_select.append_text(preset.name());
+    }
+    for name in views.iter() {
+        view_select.append_text(name);
+    }
+
+    // handler of the select sets the same model
+    let model_select: gtk::ComboBoxText = builder
+        .get_object(constants::FORCE_MODEL_SELECT)
+        .expect("get force_model object");
+    model_select.set_active(Some(force_model_index(force_model)));
+
+    let (index, text) = match pivot {
+        Pivot::SceneCenter => (0, String::new()),
+        Pivot::Elbow => (1, String::new()),
+        Pivot::Node(_, node) => (2, node.to_string()),
+        Pivot::Point(p) => (3, format!("{}, {}, {}", p.x, p.y, p.z)),
+    };
+    let pivot_select: gtk::ComboBoxText = builder
+        .get_object(constants::PIVOT_SELECT)
+        .expect("get pivot_select object");
+    pivot_select.set_active(Some(index));
+    let pivot_input: gtk::Entry = builder
+        .get_object(constants::PIVOT_INPUT)
+        .expect("get pivot_input object");
+    pivot_input.get_buffer().set_text(&text);
+
+    let label: gtk::Label = builder
+        .get_object(constants::LOAD_LABEL)
+        .expect("get load_label object");
+    label.set_text(&load);
+}
+
+// node is taken of the selected muscle, the point is given by its coordinates
+fn setup_pivot(builder: &gtk::Builder, controller: &Arc<Mutex<Controller>>) {
+    let select: gtk::ComboBoxText = builder
+        .get_object(constants::PIVOT_SELECT)
+        .expect("get pivot_select object");
+    for name in constants::PIVOTS.iter() {
+        select.append_text(name);
+    }
+    select.set_active(Some(0));
+
+    let input: gtk::Entry = builder
+        .get_object(constants::PIVOT_INPUT)
+        .expect("get pivot_input object");
+    let btn: gtk::Button = builder
+        .get_object(constants::PIVOT_BTN)
+        .expect("get pivot_btn object");
+    btn.connect_clicked(clone!(controller => move |_| {
+        let text = input.get_buffer().get_text();
+        let pivot = match select.get_active() {
+            Some(1) => Pivot::Elbow,
+            Some(2) => match parse_or_show_err(text.trim().to_string()) {
+                Ok(node) => Pivot::Node(controller.lock().unwrap().selected(), node),
+                Err(_) => return,
+            },
+            Some(3) => match parse_point(&text) {
+                Ok(p) => Pivot::Point(p),
+                Err(_) => return,
+            },
+            _ => Pivot::SceneCenter,
+        };
+        // error is shown after the controller is unlocked for the frame timer
+        let result = controller.lock().unwrap().set_pivot(pivot);
+        if let Err(text) = result {
+            show_error(text);
+        }
+    }));
+}
+
+// "x, y, z"
+fn parse_point(text: &str) -> Result<Point3d, ()> {
+    let coords = text
+        .split(',')
+        .map(|coord| parse_or_show_err(coord.trim().to_string()))
+        .collect::<Result<Vec<f64>, ()>>()?;
+    match coords.as_slice() {
+        &[x, y, z] => Ok(Point3d::new(x, y, z)),
+        _ => {
+            show_error(format!("Point must have 3 coordinates: {}", text));
+            Err(())
+        }
+    }
+}
+
+// presets and saved views are applied, when chosen, so the same view can be chosen again
+fn setup_views(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let select: gtk::ComboBoxText = builder
+        .get_object(constants::VIEW_SELECT)
+        .expect("get view_select object");
+    for preset in ViewPreset::ALL.iter() {
+        select.append_text(preset.name());
+    }
+    select.connect_changed(clone!(controller, drawing_area => move |select| {
+        let name = match select.get_active_text() {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+        let result = {
+            let mut controller = controller.lock().unwrap();
+            match ViewPreset::ALL.iter().find(|preset| preset.name() == name) {
+                Some(&preset) => {
+                    controller.set_view(preset);
+                    Ok(())
+                }
+                None => controller.restore_view(&name),
+            }
+        };
+        if let Err(text) = result {
+            show_error(text);
+        }
+        select.set_active(None);
+        drawing_area.queue_draw();
+    }));
+
+    let fit_btn: gtk::Button = builder
+        .get_object(constants::FIT_BTN)
+        .expect("get fit_btn object");
+    fit_btn.connect_clicked(clone!(controller, drawing_area, builder => move |_| {
+        process_command(&controller, &drawing_area, &builder, Command::ZoomToFit);
+    }));
+
+    let input: gtk::Entry = builder
+        .get_object(constants::VIEW_NAME_INPUT)
+        .expect("get view_name object");
+    let save_btn: gtk::Button = builder
+        .get_object(constants::SAVE_VIEW_BTN)
+        .expect("get save_view_btn object");
+    save_btn.connect_clicked(clone!(controller => move |_| {
+        let name = input.get_buffer().get_text();
+        let (known, result) = {
+            let mut controller = controller.lock().unwrap();
+            let known = controller.view_names().contains(&name.trim().to_string());
+            (known, controller.save_view(&name))
+        };
+        match result {
+            Ok(_) if !known => select.append_text(name.trim()),
+            Ok(_) => (),
+            Err(text) => show_error(text),
+        }
+    }));
+}
+
+fn setup_explore(builder: &gtk::Builder, controller: &Arc<Mutex<Controller>>) {
+    let input: gtk::Entry = builder
+        .get_object(constants::RANGE_SAMPLES_INPUT)
+        .expect("get range_samples object");
+    let btn: gtk::Button = builder
+        .get_object(constants::EXPLORE_BTN)
+        .expect("get explore_btn object");
+    btn.connect_clicked(clone!(controller => move |_| {
+        let text = input.get_buffer().get_text();
+        let samples = if text.trim().is_empty() {
+            constants::RANGE_SAMPLES
+        } else {
+            match parse_or_show_err(text) {
+                Ok(val) => val,
+                Err(_) => return,
+            }
+        };
+        if samples < 2 {
+            show_error(format!("Too few samples: {}", samples));
+            return;
+        }
+        let report = controller.lock().unwrap().explore_range(samples).report();
+        show_message(gtk::MessageType::Info, "Admissible range", &report);
+    }));
+}
+
+fn setup_metrics(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let label: gtk::Label = builder
+        .get_object(constants::METRICS_LABEL)
+        .expect("get metrics_label object");
+    drawing_area.connect_draw(clone!(controller => move |_, _| {
+        let text = format_metrics(&controller.lock().unwrap().metrics());
+        label.set_text(&text);
+        Inhibit(false)
+    }));
+
+    // recording stops, when the button is released, and the history is saved
+    let btn: gtk::ToggleButton = builder
+        .get_object(constants::RECORD_BTN)
+        .expect("get record_btn object");
+    btn.connect_toggled(clone!(controller => move |btn| {
+        if btn.get_active() {
+            controller.lock().unwrap().start_recording();
+            return;
+        }
+        let recorder = match controller.lock().unwrap().stop_recording() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        if let Some(path) = choose_file("Save metrics", gtk::FileChooserAction::Save) {
+            if let Err(err) = recorder.write_csv(&path) {
+                show_error(format!("Can't save metrics!\n{}", err));
+            }
+        }
+    }));
+}
+
+fn format_metrics(metrics: &MuscleMetrics) -> String {
+    let mut text = format!(
+        "Angle: {:.1}°\nLength: {:.1}\nVolume: {:.0}\nVolume drift: {:.2e}\n",
+        metrics.angle.to_degrees(),
+        metrics.len,
+        metrics.volume,
+        metrics.volume_drift
+    );
+    text += &format!(
+        "Radius: max {:.1}, mean {:.1}\nAreas:",
+        metrics.max_radius, metrics.mean_radius
+    );
+    for (i, area) in metrics.areas.iter().enumerate() {
+        if i % constants::AREAS_PER_LINE == 0 {
+            text += "\n";
+        }
+        text += &format!("{:>8.0}", area);
+    }
+    text
+}
+
+fn setup_smooth(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let filter_select: gtk::ComboBoxText = builder
+        .get_object(constants::SMOOTH_FILTER_SELECT)
+        .expect("get smooth_filter object");
+    for name in constants::SMOOTH_FILTERS.iter() {
+        filter_select.append(Some(name), name);
+    }
+    filter_select.set_active(Some(0));
+    let input: gtk::Entry = builder
+        .get_object(constants::SMOOTH_STRENGTH_INPUT)
+        .expect("get smooth_strength object");
+    let gm_check: gtk::CheckButton = builder
+        .get_object(constants::SMOOTH_GM_CHECK)
+        .expect("get smooth_gm object");
+    let btn: gtk::Button = builder
+        .get_object(constants::SMOOTH_BTN)
+        .expect("get smooth_btn object");
+
+    btn.connect_clicked(clone!(controller, drawing_area => move |_| {
+        let strength = match parse_or_show_err(input.get_buffer().get_text()) {
+            Ok(val) => val,
+            Err(_) => return,
+        };
+        let filter = match filter_select.get_active() {
+            Some(1) => SmoothFilter::Laplacian,
+            _ => SmoothFilter::Gaussian,
+        };
+        let params = SmoothParams::new(filter, strength, gm_check.get_active());
+        let result = {
+            let mut controller = controller.lock().unwrap();
+            let result = controller.restruct_muscle(MuscleOperation::Smooth(params));
+            controller.update_pixbuf();
+            result
+        };
+        if let Err(text) = result {
+            show_error(text);
+        }
+        drawing_area.queue_draw();
+    }));
+}
+
+fn setup_resample(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let input: gtk::Entry = builder
+        .get_object(constants::NODES_INPUT)
+        .expect("get nodes_input object");
+    let btn: gtk::Button = builder
+        .get_object(constants::RESAMPLE_BTN)
+        .expect("get resample_btn object");
+    btn.connect_clicked(clone!(controller, drawing_area => move |_| {
+        let n = match parse_or_show_err(input.get_buffer().get_text()) {
+            Ok(val) => val,
+            Err(_) => return,
+        };
+        let result = {
+            let mut controller = controller.lock().unwrap();
+            let result = controller.restruct_muscle(MuscleOperation::Resample(n));
+            controller.update_pixbuf();
+            result
+        };
+        if let Err(text) = result {
+            show_error(text);
+        }
+        drawing_area.queue_draw();
+    }));
+}
+
+fn setup_load(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let input: gtk::Entry = builder
+        .get_object(constants::MASS_INPUT)
+        .expect("get mass_input object");
+    let btn: gtk::Button = builder
+        .get_object(constants::LOAD_BTN)
+        .expect("get load_btn object");
+    btn.connect_clicked(clone!(controller, drawing_area => move |_| {
+        let mass = match parse_or_show_err(input.get_buffer().get_text()) {
+            Ok(val) => val,
+            Err(_) => return,
+        };
+        let result = {
+            let mut controller = controller.lock().unwrap();
+            controller.set_load(mass).map(|_| controller.update_pixbuf())
+        };
+        if let Err(text) = result {
+            show_error(text);
+            return;
+        }
+        drawing_area.queue_draw();
+    }));
+
+    let label: gtk::Label = builder
+        .get_object(constants::LOAD_LABEL)
+        .expect("get load_label object");
+    drawing_area.connect_draw(clone!(controller => move |_, _| {
+        let text = load_text(&controller.lock().unwrap());
+        label.set_text(&text);
+        Inhibit(false)
+    }));
 }
 
+fn load_text(controller: &Controller) -> String {
+    match controller.load_result() {
+        Some((load, result)) => format_load(&load, &result),
+        None => "No load".to_string(),
+    }
+}
+
+fn format_load(load: &Load, result: &LoadResult) -> String {
+    let activation = match result.activation {
+        Some(val) => format!("{:.2}", val),
+        None => "-".to_string(),
+    };
+    let mut text = format!(
+        "Mass: {:.1} kg\nF = {:.1} N, activation = {}\n",
+        load.mass, result.force, activation
+    );
+    if !result.holdable {
+        text += "Muscle can't hold the load!\n";
+    }
+    text + &format!("Stretch: {:.2}, bulge: {:.2}", result.stretch, result.bulge)
+}
+
+fn setup_mechanics(
+    builder: &gtk::Builder,
+    controller: &Arc<Mutex<Controller>>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    let model_select: gtk::ComboBoxText = builder
+        .get_object(constants::FORCE_MODEL_SELECT)
+        .expect("get force_model object");
+    for name in constants::FORCE_MODELS.iter() {
+        model_select.append(Some(name), name);
+    }
+    model_select.set_active(Some(0));
+    model_select.connect_changed(clone!(controller, drawing_area => move |select| {
+        let model = match select.get_active() {
+            Some(1) => ForceModel::Linear,
+            _ => ForceModel::Hill,
+        };
+        controller.lock().unwrap().set_force_model(model);
+        drawing_area.queue_draw();
+    }));
+
+    let input: gtk::Entry = builder
+        .get_object(constants::ACTIVATION_INPUT)
+        .expect("get activation_input object");
+    let btn: gtk::Button = builder
+        .get_object(constants::ACTIVATION_BTN)
+        .expect("get activation_btn object");
+    btn.connect_clicked(clone!(controller, drawing_area => move |_| {
+        let activation = match parse_or_show_err(input.get_buffer().get_text()) {
+            Ok(val) => val,
+            Err(_) => return,
+        };
+        let result = controller.lock().unwrap().set_activation(activation);
+        if let Err(text) = result {
+            show_error(text);
+        }
+        drawing_area.queue_draw();
+    }));
+
+    // label is refreshed on every redraw of the model
+    let label: gtk::Label = builder
+        .get_object(constants::MECHANICS_LABEL)
+        .expect("get mechanics_label object");
+    drawing_area.connect_draw(clone!(controller => move |_, _| {
+        let text = {
+            let controller = controller.lock().unwrap();
+            format_mechanics(&controller.muscle_names(), &controller.mechanics())
+        };
+        label.set_text(&text);
+        Inhibit(false)
+    }));
+
+    let export_btn: gtk::Button = builder
+        .get_object(constants::EXPORT_SWEEP_BTN)
+        .expect("get export_sweep object");
+    export_btn.connect_clicked(clone!(controller => move |_| {
+        if let Some(path) = choose_file("Export sweep", gtk::FileChooserAction::Save) {
+            let result = controller.lock().unwrap().export_sweep(&path);
+            if let Err(text) = result {
+                show_error(text);
+            }
+        }
+    }));
+}
+
+fn format_mechanics(names: &[String], mechanics: &JointMechanics) -> String {
+    let mut text = format!("Angle: {:.1}°\n", mechanics.angle.to_degrees());
+    for (name, m) in names.iter().zip(mechanics.muscles.iter()) {
+        text += &format!(
+            "{}: F = {:.1} N, arm = {:.1}, τ = {:.2} N·m\n",
+            name, m.force, m.moment_arm, m.torque
+        );
+    }
+    text + &format!("Joint torque: {:.2} N·m", mechanics.torque)
+}
+
+pub fn choose_file(title: &str, action: gtk::FileChooserAction) -> Option<std::path::PathBuf> {
+    let accept = match action {
+        gtk::FileChooserAction::Save => "_Save",
+        _ => "_Open",
+    };
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(title),
+        None::<&gtk::Window>,
+        action,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            (accept, gtk::ResponseType::Accept),
+        ],
+    );
+    let path = match dialog.run() {
+        gtk::ResponseType::Accept => dialog.get_filename(),
+        _ => None,
+    };
+    dialog.close();
+    path
+}
+
+fn setup_muscle_select(builder: &gtk::Builder, controller: &Arc<Mutex<Controller>>) {
+    let select: gtk::ComboBoxText = builder
+        .get_object(constants::MUSCLE_SELECT)
+        .expect("get muscle_select object");
+    for name in controller.lock().unwrap().muscle_names() {
+        select.append(Some(&name), &name);
+    }
+    select.set_active(Some(0));
+
+    let model_select: gtk::ComboBoxText = builder
+        .get_object(constants::DEFORMATION_SELECT)
+        .expect("get deformation_select object");
+    for name in constants::DEFORMATION_MODELS.iter() {
+        model_select.append(Some(name), name);
+    }
+    let model = controller.lock().unwrap().deformation_model();
+    model_select.set_active(Some(deformation_index(model)));
+
+    select.connect_changed(clone!(controller, model_select => move |select| {
+        if let Some(name) = select.get_active_id() {
+            let result = {
+                let mut controller = controller.lock().unwrap();
+                controller
+                    .select_muscle(&name)
+                    .map(|_| controller.deformation_model())
+            };
+            // selected muscle may have its own model, the handler of the model select locks
+            // the controller
+            match result {
+                Ok(model) => model_select.set_active(Some(deformation_index(model))),
+                Err(text) => show_error(text),
+            }
+        }
+    }));
+
+    model_select.connect_changed(clone!(controller => move |select| {
+        let model = match select.get_active() {
+            Some(1) => DeformationKind::UniformScaling,
+            Some(2) => DeformationKind::PerSegment,
+            _ => DeformationKind::GrowMults,
+        };
+        controller.lock().unwrap().set_deformation_model(model);
+    }));
+}
+
+// index of the model in `FORCE_MODELS`
+fn force_model_index(model: ForceModel) -> u32 {
+    match model {
+        ForceModel::Hill => 0,
+        ForceModel::Linear => 1,
+    }
+}
+
+// index of the model in `DEFORMATION_MODELS`
+fn deformation_index(model: DeformationKind) -> u32 {
+    match model {
+        DeformationKind::GrowMults => 0,
+        DeformationKind::UniformScaling => 1,
+        DeformationKind::PerSegment => 2,
+    }
+}
+
+// inputs are cloned into the handlers
+#[allow(clippy::ptr_arg)]
 fn setup_add(
-    rbtns: &Vec<gtk::Button>,
+    rbtns: &[gtk::Button],
     inputs: &Vec<gtk::Entry>,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
@@ -62,13 +708,15 @@ fn setup_add(
                 Ok(res) => res,
                 Err(_) => return,
             };
-            {
+            let result = {
                 let mut controller = controller.lock().unwrap();
-                if let Err(text) =
-                    controller.restruct_muscle(MuscleOperation::Add(MOParams::new(pos, rad, gm))) {
-                        show_error(text.to_string());
-                }
+                let result =
+                    controller.restruct_muscle(MuscleOperation::Add(MOParams::new(pos, rad, gm)));
                 controller.update_pixbuf();
+                result
+            };
+            if let Err(text) = result {
+                show_error(text);
             }
 
             drawing_area.queue_draw();
@@ -76,8 +724,9 @@ fn setup_add(
     );
 }
 
+#[allow(clippy::ptr_arg)]
 fn setup_mod(
-    rbtns: &Vec<gtk::Button>,
+    rbtns: &[gtk::Button],
     inputs: &Vec<gtk::Entry>,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
@@ -88,13 +737,18 @@ fn setup_mod(
                 Ok(res) => res,
                 Err(_) => return,
             };
-            {
+            let falloff = match parse_falloff(&inputs) {
+                Ok(val) => val,
+                Err(_) => return,
+            };
+            let result = {
                 let mut controller = controller.lock().unwrap();
-                if let Err(text) =
-                    controller.restruct_muscle(MuscleOperation::Mod(MOParams::new(pos, rad, gm))) {
-                        show_error(text.to_string());
-                }
+                let result = controller.restruct_muscle(edit_operation(pos, rad, gm, falloff));
                 controller.update_pixbuf();
+                result
+            };
+            if let Err(text) = result {
+                show_error(text);
             }
 
             drawing_area.queue_draw();
@@ -102,8 +756,9 @@ fn setup_mod(
     );
 }
 
+#[allow(clippy::ptr_arg)]
 fn setup_del(
-    rbtns: &Vec<gtk::Button>,
+    rbtns: &[gtk::Button],
     inputs: &Vec<gtk::Entry>,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
@@ -115,13 +770,15 @@ fn setup_del(
                 Err(_) => return,
             };
 
-            {
+            let result = {
                 let mut controller = controller.lock().unwrap();
-                if let Err(text) = controller.restruct_muscle(MuscleOperation::Del(pos)) {
-                    show_error(text.to_string());
-                    return;
-                }
-                controller.update_pixbuf();
+                controller
+                    .restruct_muscle(MuscleOperation::Del(pos))
+                    .map(|_| controller.update_pixbuf())
+            };
+            if let Err(text) = result {
+                show_error(text);
+                return;
             }
 
             drawing_area.queue_draw();
@@ -129,8 +786,9 @@ fn setup_del(
     );
 }
 
+#[allow(clippy::ptr_arg)]
 fn setup_rpm(
-    rbtns: &Vec<gtk::Button>,
+    rbtns: &[gtk::Button],
     inputs: &Vec<gtk::Entry>,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
@@ -145,27 +803,27 @@ fn setup_rpm(
                 Ok(val) => val,
                 Err(_) => return,
             };
-            {
+            let falloff = match parse_falloff(&inputs) {
+                Ok(val) => val,
+                Err(_) => return,
+            };
+            let result = {
                 let mut controller = controller.lock().unwrap();
-                let (mut rad, mut gm) = match controller.get_node(pos) {
-                    Ok(val) => val,
-                    Err(txt) => {
-                        show_error(txt);
-                        return;
-                    },
-                };
-                match i1 {
-                    constants::MODP_BTN => rad += constants::DELTA_VAL,
-                    constants::MODPG_BTN => gm += constants::DELTA_VAL,
-                    _ => unreachable!("only 2 variants"),
-                }
-                inputs[constants::RAD_INPUT].get_buffer().set_text(&rad.to_string());
-                inputs[constants::GM_INPUT].get_buffer().set_text(&gm.to_string());
-                if let Err(text) =
-                    controller.restruct_muscle(MuscleOperation::Mod(MOParams::new(pos, rad, gm))) {
-                        show_error(text.to_string());
-                }
+                let result = controller.get_node(pos).and_then(|(mut rad, mut gm)| {
+                    match i1 {
+                        constants::MODP_BTN => rad += constants::DELTA_VAL,
+                        constants::MODPG_BTN => gm += constants::DELTA_VAL,
+                        _ => unreachable!("only 2 variants"),
+                    }
+                    inputs[constants::RAD_INPUT].get_buffer().set_text(&rad.to_string());
+                    inputs[constants::GM_INPUT].get_buffer().set_text(&gm.to_string());
+                    controller.restruct_muscle(edit_operation(pos, rad, gm, falloff))
+                });
                 controller.update_pixbuf();
+                result
+            };
+            if let Err(text) = result {
+                show_error(text);
             }
 
             drawing_area.queue_draw();
@@ -178,27 +836,27 @@ fn setup_rpm(
                 Ok(val) => val,
                 Err(_) => return,
             };
-            {
+            let falloff = match parse_falloff(&inputs) {
+                Ok(val) => val,
+                Err(_) => return,
+            };
+            let result = {
                 let mut controller = controller.lock().unwrap();
-                let (mut rad, mut gm) = match controller.get_node(pos) {
-                    Ok(val) => val,
-                    Err(txt) => {
-                        show_error(txt);
-                        return;
-                    },
-                };
-                match i2 {
-                    constants::MODM_BTN => rad -= constants::DELTA_VAL,
-                    constants::MODMG_BTN => gm -= constants::DELTA_VAL,
-                    _ => unreachable!("only 2 variants"),
-                }
-                inputs[constants::RAD_INPUT].get_buffer().set_text(&rad.to_string());
-                inputs[constants::GM_INPUT].get_buffer().set_text(&gm.to_string());
-                if let Err(text) =
-                    controller.restruct_muscle(MuscleOperation::Mod(MOParams::new(pos, rad, gm))) {
-                        show_error(text.to_string());
-                }
+                let result = controller.get_node(pos).and_then(|(mut rad, mut gm)| {
+                    match i2 {
+                        constants::MODM_BTN => rad -= constants::DELTA_VAL,
+                        constants::MODMG_BTN => gm -= constants::DELTA_VAL,
+                        _ => unreachable!("only 2 variants"),
+                    }
+                    inputs[constants::RAD_INPUT].get_buffer().set_text(&rad.to_string());
+                    inputs[constants::GM_INPUT].get_buffer().set_text(&gm.to_string());
+                    controller.restruct_muscle(edit_operation(pos, rad, gm, falloff))
+                });
                 controller.update_pixbuf();
+                result
+            };
+            if let Err(text) = result {
+                show_error(text);
             }
 
             drawing_area.queue_draw();
@@ -207,29 +865,41 @@ fn setup_rpm(
     }
 }
 
-fn setup_next_prev(rbtns: &Vec<gtk::Button>, inputs: &Vec<gtk::Entry>) {
+#[allow(clippy::ptr_arg)]
+fn setup_next_prev(rbtns: &[gtk::Button], inputs: &Vec<gtk::Entry>) {
     rbtns[constants::NEXT_BTN].connect_clicked(clone!(inputs => move |_| {
         let mut pos =
-            match parse_or_show_err::<usize>(inputs[constants::POS_INPUT].get_buffer().get_text()) {
-                Ok(val) => val,
-                Err(_) => 0,
-            };
+            parse_or_show_err::<usize>(inputs[constants::POS_INPUT].get_buffer().get_text()).unwrap_or_default();
         pos += 1;
         inputs[constants::POS_INPUT].get_buffer().set_text(&pos.to_string());
     }));
 
     rbtns[constants::PREV_BTN].connect_clicked(clone!(inputs => move |_| {
         let mut pos =
-            match parse_or_show_err::<usize>(inputs[constants::POS_INPUT].get_buffer().get_text()) {
-                Ok(val) => val,
-                Err(_) => 0,
-            };
+            parse_or_show_err::<usize>(inputs[constants::POS_INPUT].get_buffer().get_text()).unwrap_or_default();
         pos -= 1;
         inputs[constants::POS_INPUT].get_buffer().set_text(&pos.to_string());
     }));
 }
 
-fn parse_all(inputs: &Vec<gtk::Entry>) -> Result<(usize, f64, f64), ()> {
+// falloff of the node edit, None if it isn't set
+fn parse_falloff(inputs: &[gtk::Entry]) -> Result<Option<f64>, ()> {
+    let text = inputs[constants::FALLOFF_INPUT].get_buffer().get_text();
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    parse_or_show_err(text.trim().to_string()).map(Some)
+}
+
+// node edit, which changes the neighbours too if the falloff is set
+fn edit_operation(pos: usize, rad: f64, gm: f64, falloff: Option<f64>) -> MuscleOperation {
+    match falloff {
+        Some(falloff) => MuscleOperation::SoftMod(MOParams::new(pos, rad, gm), falloff),
+        None => MuscleOperation::Mod(MOParams::new(pos, rad, gm)),
+    }
+}
+
+fn parse_all(inputs: &[gtk::Entry]) -> Result<(usize, f64, f64), ()> {
     let pos = parse_or_show_err(inputs[constants::POS_INPUT].get_buffer().get_text())?;
     let rad = parse_or_show_err(inputs[constants::RAD_INPUT].get_buffer().get_text())?;
     let gm = parse_or_show_err(inputs[constants::GM_INPUT].get_buffer().get_text())?;
@@ -246,44 +916,49 @@ fn parse_or_show_err<T: std::str::FromStr>(text: String) -> Result<T, ()> {
     }
 }
 
-fn parse_light_pos(inputs: &Vec<gtk::Entry>) -> Result<Point3d, ()> {
+fn parse_light_pos(inputs: &[gtk::Entry]) -> Result<Point3d, ()> {
     let x = parse_or_show_err(inputs[constants::X_INPUT].get_buffer().get_text())?;
     let y = parse_or_show_err(inputs[constants::Y_INPUT].get_buffer().get_text())?;
     let z = parse_or_show_err(inputs[constants::Z_INPUT].get_buffer().get_text())?;
     Ok(Point3d::new(x, y, z))
 }
 
-fn show_error(text: String) {
+pub fn show_error(text: String) {
+    show_message(gtk::MessageType::Error, "Error", &text);
+}
+
+fn show_message(kind: gtk::MessageType, title: &str, text: &str) {
     let dialog = gtk::MessageDialog::new(
         None::<&gtk::Window>,
         gtk::DialogFlags::empty(),
-        gtk::MessageType::Error,
+        kind,
         gtk::ButtonsType::None,
-        &text,
+        text,
     );
-    dialog.set_title("Error");
+    dialog.set_title(title);
     dialog.run();
 }
 
+#[allow(clippy::ptr_arg)]
 fn setup_light_button(
-    rbtns: &Vec<gtk::Button>,
+    rbtns: &[gtk::Button],
     inputs: &Vec<gtk::Entry>,
     controller: &Arc<Mutex<Controller>>,
     drawing_area: &gtk::DrawingArea,
 ) {
     rbtns[constants::MOVE_LS].connect_clicked(
-    clone!(inputs, controller, drawing_area => move |_| {
-        let p = match parse_light_pos(&inputs) {
-            Ok(val) => val,
-            Err(_) => return,
-        };
-        {
-            let mut controller = controller.lock().unwrap();
-            controller.move_light_source(p);
-            controller.update_pixbuf();
-        }
+        clone!(inputs, controller, drawing_area => move |_| {
+            let p = match parse_light_pos(&inputs) {
+                Ok(val) => val,
+                Err(_) => return,
+            };
+            {
+                let mut controller = controller.lock().unwrap();
+                controller.move_light_source(p);
+                controller.update_pixbuf();
+            }
 
-        drawing_area.queue_draw();
-    }),
+            drawing_area.queue_draw();
+        }),
     );
 }
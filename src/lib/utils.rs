@@ -3,60 +3,138 @@ use std::env;
 use std::f64;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use super::prelude::*;
 
-pub fn solve_quad_eq(a: f64, b: f64, c: f64) -> (Option<f64>, Option<f64>) {
-    if relative_eq!(a, 0_f64) {
-        if relative_eq!(b, 0_f64) {
-            (None, None)
+// Moves from `x` by steps growing twice up to `limit` until the sign of `f` changes.
+// Returns the interval with the root or None if `limit` is reached without it.
+pub fn expand_bracket<F: Fn(f64) -> f64>(
+    f: F,
+    x: f64,
+    step: f64,
+    limit: f64,
+    iterations: usize,
+) -> Option<(f64, f64)> {
+    let (mut prev, mut step) = (x, step.abs() * (limit - x).signum());
+    let mut prev_val = f(prev);
+    for _ in 0..iterations {
+        let next = if (limit - prev).abs() <= step.abs() {
+            limit
         } else {
-            (Some(c / b), None)
+            prev + step
+        };
+        let next_val = f(next);
+        if prev_val * next_val <= 0_f64 {
+            return Some((f64::min(prev, next), f64::max(prev, next)));
         }
-    } else {
-        let det = b * b - 4_f64 * a * c;
-        if relative_eq!(det, 0_f64) {
-            (Some(-b / 2_f64 * a), None)
-        } else if det < 0_f64 {
-            (None, None)
+        if next == limit {
+            return None;
+        }
+        prev = next;
+        prev_val = next_val;
+        step *= 2_f64;
+    }
+    None
+}
+
+// Newton method safeguarded by bisection: `f` returns the value and the derivative,
+// `f(low)` and `f(high)` have different signs. Returns the root, the number of made
+// iterations and whether |f| became less than `tolerance`.
+pub fn solve_bracketed<F: Fn(f64) -> (f64, f64)>(
+    f: F,
+    mut low: f64,
+    mut high: f64,
+    tolerance: f64,
+    iterations: usize,
+) -> (f64, usize, bool) {
+    let low_positive = f(low).0 > 0_f64;
+    let mut x = (low + high) / 2_f64;
+    for i in 0..iterations {
+        let (val, der) = f(x);
+        if val.abs() <= tolerance {
+            return (x, i, true);
+        }
+        if (val > 0_f64) == low_positive {
+            low = x;
         } else {
-            let dsqrt = f64::sqrt(det);
-            (
-                Some((-b - dsqrt) / 2_f64 / a),
-                Some((-b + dsqrt) / 2_f64 / a),
-            )
+            high = x;
         }
+        let newton = x - val / der;
+        x = if der != 0_f64 && newton > low && newton < high {
+            newton
+        } else {
+            (low + high) / 2_f64
+        };
     }
+    let converged = f(x).0.abs() <= tolerance;
+    (x, iterations, converged)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MuscleConfig {
+    pub name: String,
     pub radiuses: Vec<f64>,
     pub grow_mults: Vec<f64>,
     pub len: f64,
+    #[serde(default)]
+    pub color: Option<u32>,
+    // [origin, insertion] distances from the elbow (see `Attachment`)
+    #[serde(default)]
+    pub attachment: Option<[f64; 2]>,
+    // parameters of the Hill-type model (estimated from the profile if not set)
+    #[serde(default)]
+    pub hill: Option<HillParams>,
+    #[serde(default)]
+    pub excitation: Excitation,
+    // allowed relative error of the volume on deformation
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    #[serde(default)]
+    pub deformation: DeformationKind,
+    // measured profiles, between which radiuses are interpolated
+    #[serde(default)]
+    pub keyframes: Vec<ProfileKeyframe>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileKeyframe {
+    // flexion angle in degrees (0 is the extended arm)
+    pub angle: f64,
+    pub radiuses: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CarcassConfig {
     pub data: [[f64; 2]; 2],
     pub thickness: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
-    pub muscle_config: MuscleConfig,
+    pub muscles: Vec<MuscleConfig>,
     pub carcass_config: CarcassConfig,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
 }
 
-pub fn read_from_config() -> Config {
-    let mut config_path = env::current_dir().expect("Current directory");
+// config of the current directory, which is used without `--config`
+pub fn default_config_path() -> Result<PathBuf, String> {
+    let mut config_path =
+        env::current_dir().map_err(|err| format!("Can't get current directory!\n{}", err))?;
     for elem in constants::RELATIVE_CONF_PATH.iter() {
         config_path.push(elem);
     }
-    let reader =
-        File::open(config_path.to_str().expect("File to string")).expect("Open config file");
-    let reader = BufReader::new(reader);
-    serde_yaml::from_reader(reader).expect("Data from config")
+    Ok(config_path)
+}
+
+// parsed and validated config
+pub fn read_config<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+    let reader = File::open(path).map_err(|err| format!("Can't open config!\n{}", err))?;
+    let config: Config = serde_yaml::from_reader(BufReader::new(reader))
+        .map_err(|err| format!("Bad config!\n{}", err))?;
+    validate_config(&config).map_err(|err| format!("Bad config!\n{}", err))?;
+    Ok(config)
 }
 
 pub fn cycle_extend<T: Clone>(arr: &mut Vec<T>, n: usize) {
@@ -109,7 +187,11 @@ pub fn rotate_intersections(
     {
         for (p, ep) in pts.iter().zip(vecs_eps.iter()) {
             let t = Point3d::new(p.x, p.y * f64::cos(angle), p.y * f64::sin(angle));
-            normal2points.push(Point3d::new(ep.x, ep.y * f64::cos(angle), ep.y * f64::sin(angle)));
+            normal2points.push(Point3d::new(
+                ep.x,
+                ep.y * f64::cos(angle),
+                ep.y * f64::sin(angle),
+            ));
             points.push(t);
         }
     }
@@ -121,3 +203,48 @@ pub fn angle_from_triangle(a: f64, b: f64, c: f64) -> f64 {
     let cos = (b * b + c * c - a * a) / (2_f64 * b * c);
     f64::acos(cos)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bracket_contains_root() {
+        let f = |x: f64| x * x - 10_f64;
+        let (low, high) = expand_bracket(f, 0.0, 0.5, 100.0, 20).unwrap();
+        assert!(f(low) * f(high) <= 0.0);
+        assert!(low <= 10_f64.sqrt() && 10_f64.sqrt() <= high);
+        // search goes towards the limit
+        let (low, high) = expand_bracket(f, 0.0, 0.5, -100.0, 20).unwrap();
+        assert!(low <= -(10_f64.sqrt()) && -(10_f64.sqrt()) <= high);
+    }
+
+    #[test]
+    fn bracket_fails_without_root() {
+        let f = |x: f64| x * x + 1_f64;
+        assert!(expand_bracket(f, 0.0, 0.5, 100.0, 100).is_none());
+        // root is behind the limit
+        assert!(expand_bracket(|x| x - 5_f64, 0.0, 1.0, 4.0, 100).is_none());
+        // iterations end before the root
+        assert!(expand_bracket(|x| x - 1000_f64, 0.0, 1.0, 1e6, 3).is_none());
+    }
+
+    #[test]
+    fn bracketed_solver_converges() {
+        let f = |x: f64| (x * x * x - 2_f64 * x - 5_f64, 3_f64 * x * x - 2_f64);
+        let (x, iterations, converged) = solve_bracketed(f, 2.0, 3.0, 1e-12, 100);
+        assert!(converged);
+        assert!(iterations < 100);
+        assert_relative_eq!(f(x).0, 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn bracketed_solver_reports_iteration_limit() {
+        // zero derivative makes the solver bisect
+        let f = |x: f64| (x - 0.3, 0_f64);
+        let (x, iterations, converged) = solve_bracketed(f, 0.0, 1.0, 1e-12, 5);
+        assert!(!converged);
+        assert_eq!(iterations, 5);
+        assert!(x > 0.0 && x < 1.0);
+    }
+}
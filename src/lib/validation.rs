@@ -0,0 +1,283 @@
+use super::prelude::*;
+
+#[derive(Clone, Debug)]
+pub enum ValidationError {
+    NoMuscles,
+    // a muscle needs at least two nodes
+    TooFewNodes {
+        nodes: usize,
+    },
+    LengthMismatch {
+        radiuses: usize,
+        grow_mults: usize,
+    },
+    NegativeRadius {
+        pos: usize,
+        rad: f64,
+    },
+    NotFinite {
+        name: &'static str,
+        pos: usize,
+    },
+    BadParameter {
+        name: &'static str,
+        value: f64,
+    },
+    // node `pos` doesn't exist for the operation `op`
+    BadPosition {
+        op: &'static str,
+        pos: usize,
+        nodes: usize,
+    },
+    Keyframed,
+    // keyframe at `angle` (degrees) has other number of nodes than the profile
+    KeyframeNodes {
+        angle: f64,
+        expected: usize,
+        got: usize,
+    },
+    // keyframes can't make the deformation model
+    Keyframes(String),
+    // muscle can't be deformed to the length given by its attachment
+    Placement(Box<DeformError>),
+    // carcass muscle with length `len` is out of the joint limit (`Carcass::len_range`)
+    JointLimit {
+        len: f64,
+        min: f64,
+        max: f64,
+    },
+    // muscle with length `len` is out of its `len_range`
+    MuscleLength {
+        len: f64,
+        min: f64,
+        max: f64,
+    },
+    // muscle with length `len` doesn't fit its attachment, which needs `expected`
+    Detached {
+        len: f64,
+        expected: f64,
+    },
+    // muscle with length `len` can't connect its attachment points
+    Triangle {
+        len: f64,
+        origin: f64,
+        insertion: f64,
+    },
+    Muscle {
+        name: String,
+        error: Box<ValidationError>,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::NoMuscles => write!(f, "No muscles in config!"),
+            ValidationError::TooFewNodes { nodes } => write!(
+                f,
+                "Too few nodes!\nnumber of nodes: {};\nmust be at least 2.",
+                nodes
+            ),
+            ValidationError::LengthMismatch {
+                radiuses,
+                grow_mults,
+            } => write!(
+                f,
+                "Numbers of radiuses and grow mults differ!\nradiuses: {};\ngrow mults: {}.",
+                radiuses, grow_mults
+            ),
+            ValidationError::NegativeRadius { pos, rad } => {
+                write!(f, "Negative radius!\npos: {};\nradius: {}.", pos, rad)
+            }
+            ValidationError::NotFinite { name, pos } => {
+                write!(f, "Bad {}!\npos: {};\nvalue is not finite.", name, pos)
+            }
+            ValidationError::BadParameter { name, value } => {
+                write!(f, "Bad {}!\nvalue: {}.", name, value)
+            }
+            ValidationError::BadPosition { op, pos, nodes } => write!(
+                f,
+                "Can't {}!\npos: {};\nnumber of nodes: {}.",
+                op, pos, nodes
+            ),
+            ValidationError::Keyframed => {
+                write!(f, "Can't edit the keyframed muscle, its profile is set by keyframes!")
+            }
+            ValidationError::KeyframeNodes {
+                angle,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Bad keyframe!\nangle: {};\nnumber of nodes: {};\nmust be {}.",
+                angle, got, expected
+            ),
+            ValidationError::Keyframes(text) => write!(f, "Bad keyframes!\n{}", text),
+            ValidationError::Placement(err) => {
+                write!(f, "Can't place muscle on the carcass!\n{}", err)
+            }
+            ValidationError::JointLimit { len, min, max } => write!(
+                f,
+                "Carcass muscle is out of the joint limit!\nlen: {};\nallowed: [{}, {}].",
+                len, min, max
+            ),
+            ValidationError::MuscleLength { len, min, max } => write!(
+                f,
+                "Muscle length is out of its range!\nlen: {};\nallowed: [{}, {}].",
+                len, min, max
+            ),
+            ValidationError::Detached { len, expected } => write!(
+                f,
+                "Muscle length doesn't fit its attachment!\nlen: {};\nexpected: {}.",
+                len, expected
+            ),
+            ValidationError::Triangle {
+                len,
+                origin,
+                insertion,
+            } => write!(
+                f,
+                "Muscle can't connect its attachment points!\nlen: {};\norigin: {};\ninsertion: {}.",
+                len, origin, insertion
+            ),
+            ValidationError::Muscle { name, error } => write!(f, "Muscle {}: {}", name, error),
+        }
+    }
+}
+
+impl From<ValidationError> for String {
+    fn from(err: ValidationError) -> Self {
+        err.to_string()
+    }
+}
+
+// at least two nodes, equal numbers of radiuses and grow multipliers, finite values
+// and non-negative radiuses
+pub fn validate_profile(radiuses: &[f64], grow_mults: &[f64]) -> Result<(), ValidationError> {
+    if radiuses.len() != grow_mults.len() {
+        return Err(ValidationError::LengthMismatch {
+            radiuses: radiuses.len(),
+            grow_mults: grow_mults.len(),
+        });
+    }
+    if radiuses.len() < 2 {
+        return Err(ValidationError::TooFewNodes {
+            nodes: radiuses.len(),
+        });
+    }
+    validate_radiuses(radiuses)?;
+    if let Some(pos) = grow_mults.iter().position(|gm| !gm.is_finite()) {
+        return Err(ValidationError::NotFinite {
+            name: "grow mult",
+            pos,
+        });
+    }
+    Ok(())
+}
+
+pub fn validate_radiuses(radiuses: &[f64]) -> Result<(), ValidationError> {
+    if let Some(pos) = radiuses.iter().position(|rad| !rad.is_finite()) {
+        return Err(ValidationError::NotFinite {
+            name: "radius",
+            pos,
+        });
+    }
+    if let Some((pos, &rad)) = radiuses.iter().enumerate().find(|(_, &rad)| rad < 0_f64) {
+        return Err(ValidationError::NegativeRadius { pos, rad });
+    }
+    Ok(())
+}
+
+// `value` must be finite and greater than zero (or not less than zero if `zero` is allowed)
+pub fn validate_positive(
+    name: &'static str,
+    value: f64,
+    zero: bool,
+) -> Result<(), ValidationError> {
+    if !value.is_finite() || value < 0_f64 || (value == 0_f64 && !zero) {
+        return Err(ValidationError::BadParameter { name, value });
+    }
+    Ok(())
+}
+
+// carcass muscle is in the joint limit
+pub fn validate_joint(carcass: &Carcass) -> Result<(), ValidationError> {
+    if !carcass.in_joint_limit() {
+        let (min, max) = carcass.len_range();
+        return Err(ValidationError::JointLimit {
+            len: carcass.len(),
+            min,
+            max,
+        });
+    }
+    Ok(())
+}
+
+// the muscle and two bones must form a triangle
+pub fn validate_triangle(att: &Attachment, len: f64) -> Result<(), ValidationError> {
+    let (origin, insertion) = (att.origin.abs(), att.insertion.abs());
+    if len < (origin - insertion).abs() || len > origin + insertion {
+        return Err(ValidationError::Triangle {
+            len,
+            origin: att.origin,
+            insertion: att.insertion,
+        });
+    }
+    Ok(())
+}
+
+fn validate_muscle(mconf: &MuscleConfig) -> Result<(), ValidationError> {
+    validate_profile(&mconf.radiuses, &mconf.grow_mults)?;
+    validate_positive("len", mconf.len, false)?;
+    if let Some(tolerance) = mconf.tolerance {
+        validate_positive("tolerance", tolerance, false)?;
+    }
+    if let Some([origin, insertion]) = mconf.attachment {
+        validate_positive("origin", origin.abs(), false)?;
+        validate_positive("insertion", insertion.abs(), false)?;
+    }
+    for keyframe in mconf.keyframes.iter() {
+        if !keyframe.angle.is_finite() {
+            return Err(ValidationError::BadParameter {
+                name: "keyframe angle",
+                value: keyframe.angle,
+            });
+        }
+        if keyframe.radiuses.len() != mconf.radiuses.len() {
+            return Err(ValidationError::KeyframeNodes {
+                angle: keyframe.angle,
+                expected: mconf.radiuses.len(),
+                got: keyframe.radiuses.len(),
+            });
+        }
+        validate_radiuses(&keyframe.radiuses)?;
+    }
+    Ok(())
+}
+
+// Checks the whole config: profiles of the muscles, sizes of the carcass and the
+// length of the first muscle, which sets the initial angle in the elbow.
+pub fn validate_config(config: &Config) -> Result<(), ValidationError> {
+    let first = config.muscles.first().ok_or(ValidationError::NoMuscles)?;
+    for mconf in config.muscles.iter() {
+        validate_muscle(mconf).map_err(|err| ValidationError::Muscle {
+            name: mconf.name.clone(),
+            error: Box::new(err),
+        })?;
+    }
+
+    let cconf = &config.carcass_config;
+    for &size in cconf.data.iter().flatten() {
+        validate_positive("carcass size", size, false)?;
+    }
+    validate_positive("carcass thickness", cconf.thickness, true)?;
+
+    let att = match first.attachment {
+        Some([origin, insertion]) => Attachment::new(origin, insertion),
+        None => Attachment::new(cconf.data[0][1], cconf.data[1][0]),
+    };
+    validate_triangle(&att, first.len).map_err(|err| ValidationError::Muscle {
+        name: first.name.clone(),
+        error: Box::new(err),
+    })
+}
@@ -1,18 +1,39 @@
+// `lib` is a module of the binary, the crate has no library target
+#![allow(special_module_name)]
+
 #[macro_use]
 extern crate approx;
 
 use gio::prelude::*;
 
 mod lib;
-use lib::prelude::build_ui;
+use lib::prelude::{build_ui, compare_command, fit_command, parse_ui_args, session_command};
 
 fn main() {
     env_logger::builder()
         .format_timestamp(None)
         .format_module_path(false)
         .init();
-    let application =
-        gtk::Application::new(None, Default::default()).expect("Init GTK application");
-    application.connect_activate(build_ui);
-    application.run(&std::env::args().collect::<Vec<_>>());
+
+    // console commands, GTK application is run without them
+    let args: Vec<String> = std::env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        Some("fit") => fit_command(&args[2..]),
+        Some("compare") => compare_command(&args[2..]),
+        Some("session") => session_command(&args[2..]),
+        _ => match parse_ui_args(&args) {
+            Ok((options, gtk_args)) => {
+                let application =
+                    gtk::Application::new(None, Default::default()).expect("Init GTK application");
+                application.connect_activate(move |app| build_ui(app, &options));
+                application.run(&gtk_args);
+                return;
+            }
+            Err(text) => Err(text),
+        },
+    };
+    if let Err(text) = result {
+        eprintln!("{}", text);
+        std::process::exit(1);
+    }
 }